@@ -0,0 +1,460 @@
+//! Deserialize the typed bindings from an [`Element`] tree.
+//!
+//! Struct fields are matched the same way as quick-xml: `@name` fields are read from
+//! attributes, `$text` from the text content and every other field from the child
+//! elements with that name. Child elements that don't match any field are handed to
+//! the [`PRESERVED`] field when the struct has one, along with the comments between
+//! them.
+
+use crate::dom::{Comment, Element, RAW_ELEMENT};
+use crate::{se, Error};
+use serde::de::{self, DeserializeOwned, Deserializer, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+/// The name of the field which receives the content that doesn't match any field.
+pub(crate) const PRESERVED: &str = "$preserved";
+
+/// The field of [`PRESERVED`] which receives unmatched child elements.
+const UNKNOWN: &str = "$unknown";
+
+/// The field of [`PRESERVED`] which receives the comments between child elements.
+pub(crate) const COMMENTS: &str = "$comments";
+
+/// The name of the field which receives an element's text content.
+const TEXT: &str = "$text";
+
+#[cfg(test)]
+pub(crate) fn from_str<T: DeserializeOwned>(xml: &str) -> Result<T, Error> {
+    from_element(&Element::parse(xml)?)
+}
+
+pub(crate) fn from_element<T: DeserializeOwned>(element: &Element) -> Result<T, Error> {
+    T::deserialize(ElementDeserializer { element })
+}
+
+impl de::Error for Error {
+    fn custom<T: std::fmt::Display>(message: T) -> Self {
+        Error::Deserialize(message.to_string())
+    }
+}
+
+/// Deserializes a single element.
+struct ElementDeserializer<'a> {
+    element: &'a Element,
+}
+
+impl ElementDeserializer<'_> {
+    fn text(&self) -> TextDeserializer {
+        TextDeserializer(self.element.text())
+    }
+}
+
+macro_rules! deserialize_text {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.text().$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ElementDeserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.element.attributes.is_empty() && self.element.elements().next().is_none() {
+            self.text().deserialize_any(visitor)
+        } else {
+            self.deserialize_map(visitor)
+        }
+    }
+
+    deserialize_text! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string deserialize_bytes
+        deserialize_byte_buf deserialize_identifier
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if name == RAW_ELEMENT {
+            visitor.visit_string(self.element.to_string())
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        ElementsDeserializer(vec![self.element]).deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(ElementMapAccess::new(self.element, &[]))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_map(ElementMapAccess::new(self.element, fields))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.text().deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// Deserializes all child elements with the same name, either as a sequence or as
+/// a single value.
+struct ElementsDeserializer<'a>(Vec<&'a Element>);
+
+impl<'a> ElementsDeserializer<'a> {
+    fn single(self) -> Result<ElementDeserializer<'a>, Error> {
+        match self.0.as_slice() {
+            [element] => Ok(ElementDeserializer { element }),
+            [element, ..] => Err(Error::Deserialize(format!(
+                "duplicate element `{}`",
+                element.name
+            ))),
+            [] => Err(Error::Deserialize("missing element".into())),
+        }
+    }
+}
+
+macro_rules! deserialize_single {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ElementsDeserializer<'_> {
+    type Error = Error;
+
+    deserialize_single! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_unit deserialize_map
+        deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.single()?.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.single()?.deserialize_newtype_struct(name, visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(ElementsAccess(self.0.into_iter()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.single()?.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+}
+
+struct ElementsAccess<'a>(std::vec::IntoIter<&'a Element>);
+
+impl<'de> de::SeqAccess<'de> for ElementsAccess<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.0
+            .next()
+            .map(|element| seed.deserialize(ElementDeserializer { element }))
+            .transpose()
+    }
+}
+
+enum Value<'a> {
+    Text(String),
+    Elements(Vec<&'a Element>),
+    Comments(Vec<Comment>),
+    Preserved(Vec<&'a Element>, Vec<Comment>),
+}
+
+/// Presents an element's attributes, child elements and text as map entries.
+struct ElementMapAccess<'a> {
+    entries: std::vec::IntoIter<(String, Value<'a>)>,
+    value: Option<Value<'a>>,
+}
+
+impl<'a> ElementMapAccess<'a> {
+    /// Group the content of the element by the given fields, or by name if there are
+    /// no fields.
+    fn new(element: &'a Element, fields: &[&str]) -> Self {
+        let mut entries: Vec<(String, Value<'a>)> = element
+            .attributes
+            .iter()
+            .map(|(key, value)| (format!("@{key}"), Value::Text(value.clone())))
+            .collect();
+
+        let mut unknown = Vec::new();
+        let mut groups: Vec<(&str, Vec<&Element>)> = Vec::new();
+        for child in element.elements() {
+            if !fields.is_empty() && !fields.contains(&child.name.as_str()) {
+                unknown.push(child);
+            } else if let Some((_, group)) = groups.iter_mut().find(|(name, _)| *name == child.name)
+            {
+                group.push(child);
+            } else {
+                groups.push((&child.name, vec![child]));
+            }
+        }
+        entries.extend(
+            groups
+                .into_iter()
+                .map(|(name, group)| (name.to_string(), Value::Elements(group))),
+        );
+
+        let comments = element.comments();
+        if fields.contains(&PRESERVED) && !(unknown.is_empty() && comments.is_empty()) {
+            entries.push((PRESERVED.into(), Value::Preserved(unknown, comments)));
+        }
+
+        let text = element.text();
+        if (fields.is_empty() || fields.contains(&TEXT)) && !text.is_empty() {
+            entries.push((TEXT.into(), Value::Text(text)));
+        }
+
+        ElementMapAccess {
+            entries: entries.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for ElementMapAccess<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        match self.value.take() {
+            Some(Value::Text(text)) => seed.deserialize(TextDeserializer(text)),
+            Some(Value::Elements(elements)) => seed.deserialize(ElementsDeserializer(elements)),
+            Some(Value::Comments(comments)) => {
+                let elements = comments
+                    .iter()
+                    .map(se::to_element)
+                    .collect::<Result<Vec<_>, _>>()?;
+                seed.deserialize(ElementsDeserializer(elements.iter().collect()))
+            }
+            Some(Value::Preserved(unknown, comments)) => {
+                seed.deserialize(PreservedDeserializer { unknown, comments })
+            }
+            None => Err(Error::Deserialize("value requested before key".into())),
+        }
+    }
+}
+
+/// Deserializes the content of an element that didn't match any field.
+struct PreservedDeserializer<'a> {
+    unknown: Vec<&'a Element>,
+    comments: Vec<Comment>,
+}
+
+impl<'de> Deserializer<'de> for PreservedDeserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let entries = vec![
+            (UNKNOWN.to_string(), Value::Elements(self.unknown)),
+            (COMMENTS.to_string(), Value::Comments(self.comments)),
+        ];
+        visitor.visit_map(ElementMapAccess {
+            entries: entries.into_iter(),
+            value: None,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+/// Deserializes a simple value from an attribute or text content.
+struct TextDeserializer(String);
+
+macro_rules! deserialize_parse {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.0.trim().parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(&self.0), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for TextDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.0)
+    }
+
+    deserialize_parse! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.0.trim().to_string().into_deserializer())
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
@@ -0,0 +1,529 @@
+//! A minimal XML element tree which backs (de)serialization and carries content that
+//! isn't modelled by the typed bindings.
+
+use crate::Error;
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::reader::NsReader;
+use quick_xml::Writer;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::{fmt, str::FromStr};
+
+/// The name of the newtype struct used to pass raw elements through serde.
+pub(crate) const RAW_ELEMENT: &str = "$serde_win_unattend::Element";
+
+pub(crate) const UNATTEND_NAMESPACE: &str = "urn:schemas-microsoft-com:unattend";
+pub(crate) const WCM_NAMESPACE: &str = "http://schemas.microsoft.com/WMIConfig/2002/State";
+pub(crate) const XSI_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema-instance";
+pub(crate) const CPI_NAMESPACE: &str = "urn:schemas-microsoft-com:cpi";
+
+/// The prefix each well-known namespace is given when parsed, whichever prefix the
+/// file itself binds it to. The unattend namespace is always the default one.
+const PREFIXES: &[(&str, &str)] = &[
+    (UNATTEND_NAMESPACE, ""),
+    (WCM_NAMESPACE, "wcm"),
+    (XSI_NAMESPACE, "xsi"),
+    (CPI_NAMESPACE, "cpi"),
+];
+
+fn prefix(namespace: &[u8]) -> Option<&'static str> {
+    PREFIXES
+        .iter()
+        .find(|(uri, _)| uri.as_bytes() == namespace)
+        .map(|(_, prefix)| *prefix)
+}
+
+/// An XML element that is preserved as-is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+/// The content of an element that isn't modelled by its type, kept so that it is
+/// written back out.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Preserved {
+    /// Child elements that don't match any setting of the type.
+    #[serde(rename = "$unknown", default)]
+    pub unknown: Vec<Element>,
+    /// Comments between the child elements.
+    #[serde(rename = "$comments", default)]
+    pub comments: Vec<Comment>,
+}
+
+/// The content of an [`Element`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+    Comment(String),
+}
+
+/// An XML comment inside a modelled element, anchored to the child element it
+/// precedes so it can be written back in place.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Comment {
+    #[serde(rename = "@text")]
+    pub text: String,
+    /// The name of the child element which follows the comment, or `None` if the
+    /// comment comes after the last child element.
+    #[serde(rename = "@before", skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    /// Which of the children with that name the comment precedes, counting from zero.
+    #[serde(rename = "@index", default)]
+    pub index: usize,
+}
+
+impl Comment {
+    /// A comment which comes before the first child element with the given name.
+    pub fn before(text: impl Into<String>, name: impl Into<String>) -> Self {
+        Comment {
+            text: text.into(),
+            before: Some(name.into()),
+            index: 0,
+        }
+    }
+
+    /// A comment which comes after all child elements.
+    pub fn trailing(text: impl Into<String>) -> Self {
+        Comment {
+            text: text.into(),
+            before: None,
+            index: 0,
+        }
+    }
+}
+
+impl Element {
+    pub fn new(name: impl Into<String>) -> Self {
+        Element {
+            name: name.into(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// The value of the attribute with the given name.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The child elements of this element.
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    /// The text content of this element, excluding that of child elements.
+    pub fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|child| match child {
+                Node::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The comments among the children of this element, anchored to the element that
+    /// follows each of them.
+    pub fn comments(&self) -> Vec<Comment> {
+        let mut comments = Vec::new();
+        let mut pending = Vec::new();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for child in &self.children {
+            match child {
+                Node::Comment(text) => pending.push(text.clone()),
+                Node::Element(element) => {
+                    let count = counts.entry(element.name.as_str()).or_default();
+                    comments.extend(pending.drain(..).map(|text| Comment {
+                        text,
+                        before: Some(element.name.clone()),
+                        index: *count,
+                    }));
+                    *count += 1;
+                }
+                Node::Text(_) => {}
+            }
+        }
+        comments.extend(pending.into_iter().map(Comment::trailing));
+        comments
+    }
+
+    /// Insert comments in front of the child elements they are anchored to. Comments
+    /// whose element no longer exists are added after the last child.
+    pub fn insert_comments(&mut self, comments: impl IntoIterator<Item = Comment>) {
+        for comment in comments {
+            let position = comment.before.as_deref().and_then(|name| {
+                self.children
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, child)| matches!(child, Node::Element(element) if element.name == name))
+                    .nth(comment.index)
+                    .map(|(position, _)| position)
+            });
+            let node = Node::Comment(comment.text);
+            match position {
+                Some(position) => self.children.insert(position, node),
+                None => self.children.push(node),
+            }
+        }
+    }
+
+    /// Parse an element, renaming prefixes of well-known namespaces to the ones used
+    /// by Windows SIM so that names can be matched literally.
+    pub(crate) fn parse(xml: &str) -> Result<Self, Error> {
+        Ok(Element::parse_document(xml)?.1)
+    }
+
+    /// Parse a document into the comments before its root element and the root
+    /// element itself.
+    pub(crate) fn parse_document(xml: &str) -> Result<(Vec<String>, Self), Error> {
+        let mut prolog = Vec::new();
+        let mut reader = NsReader::from_str(xml.strip_prefix('\u{feff}').unwrap_or(xml));
+
+        let mut stack: Vec<Element> = Vec::new();
+        let mut root = None;
+        loop {
+            let event = reader.read_event().map_err(|error| {
+                Error::Deserialize(format!("{error} at position {}", reader.buffer_position()))
+            })?;
+            match event {
+                Event::Start(start) => {
+                    let mut element = Element::from_start(&reader, &start)?;
+                    element.declare_prefixes(&stack);
+                    stack.push(element);
+                }
+                Event::Empty(start) => {
+                    let mut element = Element::from_start(&reader, &start)?;
+                    element.declare_prefixes(&stack);
+                    append(&mut stack, &mut root, element)?;
+                }
+                Event::End(_) => {
+                    let mut element = stack
+                        .pop()
+                        .ok_or_else(|| Error::Deserialize("unexpected end tag".into()))?;
+                    element.trim_indentation();
+                    append(&mut stack, &mut root, element)?;
+                }
+                Event::Text(text) => {
+                    let text = text
+                        .unescape()
+                        .map_err(|error| Error::Deserialize(error.to_string()))?;
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(Node::Text(text.into_owned()));
+                    }
+                }
+                Event::CData(data) => {
+                    let text = String::from_utf8(data.into_inner().into_owned())
+                        .map_err(|error| Error::Deserialize(error.to_string()))?;
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(Node::Text(text));
+                    }
+                }
+                Event::Comment(comment) => {
+                    let text = String::from_utf8(comment.into_inner().into_owned())
+                        .map_err(|error| Error::Deserialize(error.to_string()))?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(Node::Comment(text)),
+                        None if root.is_none() => prolog.push(text),
+                        None => {}
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        if !stack.is_empty() {
+            return Err(Error::Deserialize("unexpected end of document".into()));
+        }
+        let root = root.ok_or_else(|| Error::Deserialize("missing root element".into()))?;
+        Ok((prolog, root))
+    }
+
+    /// Drop the whitespace between child elements, while keeping the text of
+    /// elements without any exactly as it is.
+    fn trim_indentation(&mut self) {
+        if self.elements().next().is_some() {
+            self.children.retain(|child| match child {
+                Node::Text(text) => !text.trim().is_empty(),
+                _ => true,
+            });
+        }
+    }
+
+    fn from_start(reader: &NsReader<&[u8]>, start: &BytesStart) -> Result<Self, Error> {
+        let (namespace, local) = reader.resolve_element(start.name());
+        let mut element = Element::new(qualify(namespace, local.as_ref(), start.name().as_ref())?);
+        for attribute in start.attributes() {
+            let attribute = attribute.map_err(|error| Error::Deserialize(error.to_string()))?;
+            let value = attribute
+                .unescape_value()
+                .map_err(|error| Error::Deserialize(error.to_string()))?;
+            let key = if attribute.key.as_namespace_binding().is_some() {
+                match prefix(value.as_bytes()) {
+                    Some("") => "xmlns".into(),
+                    Some(prefix) => format!("xmlns:{prefix}"),
+                    None => utf8(attribute.key.as_ref())?.into(),
+                }
+            } else {
+                let (namespace, local) = reader.resolve_attribute(attribute.key);
+                qualify(namespace, local.as_ref(), attribute.key.as_ref())?
+            };
+
+            // A namespace may be bound under several prefixes, which now coincide
+            if element.attribute(&key).is_none() {
+                element.attributes.push((key, value.into_owned()));
+            }
+        }
+        Ok(element)
+    }
+
+    /// Copy the declarations of other namespaces used by this element from its
+    /// ancestors, so that it stays well-formed when kept apart from them.
+    fn declare_prefixes(&mut self, ancestors: &[Element]) {
+        let names = std::iter::once(&self.name).chain(self.attributes.iter().map(|(key, _)| key));
+        let keys: Vec<String> = names
+            .filter_map(|name| name.split_once(':'))
+            .map(|(prefix, _)| prefix)
+            .filter(|prefix| {
+                *prefix != "xmlns" && !PREFIXES.iter().any(|(_, known)| known == prefix)
+            })
+            .map(|prefix| format!("xmlns:{prefix}"))
+            .collect();
+        for key in keys {
+            if self.attribute(&key).is_some() {
+                continue;
+            }
+            let value = ancestors
+                .iter()
+                .rev()
+                .find_map(|ancestor| ancestor.attribute(&key));
+            if let Some(value) = value {
+                self.attributes.push((key, value.into()));
+            }
+        }
+    }
+
+    /// Move the `xmlns:` declarations of all descendants onto this element, unless
+    /// a descendant binds a prefix differently.
+    pub(crate) fn hoist_namespaces(&mut self) {
+        let mut declarations = Vec::new();
+        for child in &mut self.children {
+            if let Node::Element(element) = child {
+                element.take_namespaces(&mut declarations);
+            }
+        }
+        for (key, value) in declarations {
+            if self.attribute(&key).is_none() {
+                self.attributes.push((key, value));
+            }
+        }
+    }
+
+    fn take_namespaces(&mut self, declarations: &mut Vec<(String, String)>) {
+        self.attributes.retain(|(key, value)| {
+            if !key.starts_with("xmlns:") {
+                return true;
+            }
+            match declarations.iter().find(|(declared, _)| declared == key) {
+                Some((_, declared)) => declared != value,
+                None => {
+                    declarations.push((key.clone(), value.clone()));
+                    false
+                }
+            }
+        });
+        for child in &mut self.children {
+            if let Node::Element(element) = child {
+                element.take_namespaces(declarations);
+            }
+        }
+    }
+
+    /// Write the element, laying out its children on lines of their own if an indent
+    /// is given.
+    pub(crate) fn write<W: std::io::Write>(
+        &self,
+        writer: &mut Writer<W>,
+        indent: Option<Indent>,
+    ) -> Result<(), Error> {
+        self.write_at(writer, indent, 0, &mut Vec::new())
+    }
+
+    /// Write this element within the given namespace declarations, leaving out any
+    /// of its own that are already in scope.
+    fn write_at<W: std::io::Write>(
+        &self,
+        writer: &mut Writer<W>,
+        indent: Option<Indent>,
+        depth: usize,
+        scope: &mut Vec<(String, String)>,
+    ) -> Result<(), Error> {
+        let declared = scope.len();
+        let mut start = BytesStart::new(self.name.as_str());
+        for (key, value) in &self.attributes {
+            if key.starts_with("xmlns:") {
+                let in_scope = scope.iter().rev().find(|(declared, _)| declared == key);
+                if in_scope.is_some_and(|(_, declared)| declared == value) {
+                    continue;
+                }
+                scope.push((key.clone(), value.clone()));
+            }
+            start.push_attribute((key.as_str(), value.as_str()));
+        }
+
+        if self.children.is_empty() {
+            write_event(writer, Event::Empty(start))?;
+            scope.truncate(declared);
+            return Ok(());
+        }
+        write_event(writer, Event::Start(start))?;
+
+        // Only children without any text around them go on lines of their own, so
+        // that text is written exactly as it is
+        let indent = indent.filter(|_| {
+            !self
+                .children
+                .iter()
+                .any(|child| matches!(child, Node::Text(_)))
+        });
+        for child in &self.children {
+            if let Some(indent) = indent {
+                indent.write(writer, depth + 1)?;
+            }
+            match child {
+                Node::Element(element) => element.write_at(writer, indent, depth + 1, scope)?,
+                Node::Text(text) => write_event(writer, Event::Text(BytesText::new(text)))?,
+                Node::Comment(text) => {
+                    write_event(writer, Event::Comment(BytesText::from_escaped(text)))?
+                }
+            }
+        }
+        if let Some(indent) = indent {
+            indent.write(writer, depth)?;
+        }
+        write_event(writer, Event::End(BytesEnd::new(self.name.as_str())))?;
+        scope.truncate(declared);
+        Ok(())
+    }
+}
+
+/// The name of an element or attribute, using the well-known prefix of its namespace
+/// if it has one.
+fn qualify(namespace: ResolveResult, local: &[u8], name: &[u8]) -> Result<String, Error> {
+    Ok(match namespace {
+        ResolveResult::Bound(Namespace(namespace)) => match prefix(namespace) {
+            Some("") => utf8(local)?.into(),
+            Some(prefix) => format!("{prefix}:{}", utf8(local)?),
+            None => utf8(name)?.into(),
+        },
+        _ => utf8(name)?.into(),
+    })
+}
+
+fn utf8(bytes: &[u8]) -> Result<&str, Error> {
+    std::str::from_utf8(bytes).map_err(|error| Error::Deserialize(error.to_string()))
+}
+
+fn append(
+    stack: &mut [Element],
+    root: &mut Option<Element>,
+    element: Element,
+) -> Result<(), Error> {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(Node::Element(element)),
+        None if root.is_none() => *root = Some(element),
+        None => return Err(Error::Deserialize("multiple root elements".into())),
+    }
+    Ok(())
+}
+
+/// How child elements are laid out when they're written.
+#[derive(Clone, Copy)]
+pub(crate) struct Indent<'a> {
+    pub(crate) line_ending: &'a str,
+    /// The number of spaces per level of nesting.
+    pub(crate) width: usize,
+}
+
+impl Indent<'_> {
+    /// Start a new line at the given level of nesting.
+    pub(crate) fn write<W: std::io::Write>(
+        &self,
+        writer: &mut Writer<W>,
+        depth: usize,
+    ) -> Result<(), Error> {
+        let text = format!("{}{}", self.line_ending, " ".repeat(self.width * depth));
+        write_event(writer, Event::Text(BytesText::from_escaped(text)))
+    }
+}
+
+pub(crate) fn write_event<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    event: Event,
+) -> Result<(), Error> {
+    writer
+        .write_event(event)
+        .map_err(|error| Error::Serialize(error.to_string()))
+}
+
+impl FromStr for Element {
+    type Err = Error;
+
+    fn from_str(xml: &str) -> Result<Self, Self::Err> {
+        Element::parse(xml)
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut writer = Writer::new(Vec::new());
+        self.write(&mut writer, None).map_err(|_| fmt::Error)?;
+        f.write_str(&String::from_utf8_lossy(&writer.into_inner()))
+    }
+}
+
+// Raw elements travel through serde as XML text, which only this crate's
+// (de)serializers know to expand back into elements.
+impl Serialize for Element {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(RAW_ELEMENT, &self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Element {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ElementVisitor;
+
+        impl<'de> de::Visitor<'de> for ElementVisitor {
+            type Value = Element;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an XML element")
+            }
+
+            fn visit_str<E: de::Error>(self, xml: &str) -> Result<Self::Value, E> {
+                Element::parse(xml).map_err(E::custom)
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Self::Value, D::Error> {
+                deserializer.deserialize_str(self)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(RAW_ELEMENT, ElementVisitor)
+    }
+}
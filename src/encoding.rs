@@ -0,0 +1,65 @@
+//! Transcoding between the byte encodings answer files are saved in and UTF-8 text.
+
+use crate::{Encoding, Error};
+
+/// Decode an answer file using its byte order mark, or failing that the encoding
+/// named in its XML declaration.
+pub(crate) fn decode(bytes: &[u8]) -> Result<String, Error> {
+    match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => utf8(rest),
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        // UTF-16 without a byte order mark still starts with `<`
+        [b'<', 0, ..] => utf16(bytes, u16::from_le_bytes),
+        [0, b'<', ..] => utf16(bytes, u16::from_be_bytes),
+        _ => match declared_encoding(bytes).as_deref() {
+            // Files are often declared as UTF-16 but saved as single bytes, which
+            // only UTF-8 can account for at this point
+            None | Some("utf-8" | "utf8" | "us-ascii" | "utf-16" | "unicode") => utf8(bytes),
+            Some(encoding) => Err(Error::Deserialize(format!(
+                "unsupported encoding `{encoding}`"
+            ))),
+        },
+    }
+}
+
+/// Encode text for writing, adding a byte order mark to UTF-16.
+pub(crate) fn encode(text: &str, encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Utf8 => text.as_bytes().to_vec(),
+        Encoding::Utf16 => {
+            let bom = (!text.starts_with('\u{feff}')).then_some(0xFEFF);
+            bom.into_iter()
+                .chain(text.encode_utf16())
+                .flat_map(u16::to_le_bytes)
+                .collect()
+        }
+    }
+}
+
+fn utf8(bytes: &[u8]) -> Result<String, Error> {
+    String::from_utf8(bytes.to_vec()).map_err(|error| Error::Deserialize(error.to_string()))
+}
+
+fn utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> Result<String, Error> {
+    let pairs = bytes.chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return Err(Error::Deserialize("truncated UTF-16 text".into()));
+    }
+    char::decode_utf16(pairs.map(|pair| unit([pair[0], pair[1]])))
+        .collect::<Result<String, _>>()
+        .map_err(|error| Error::Deserialize(error.to_string()))
+}
+
+/// The lowercase `encoding` of an XML declaration at the start of the bytes.
+fn declared_encoding(bytes: &[u8]) -> Option<String> {
+    let declaration = bytes.strip_prefix(b"<?xml")?;
+    let declaration = &declaration[..declaration.windows(2).position(|end| end == b"?>")?];
+    let declaration = std::str::from_utf8(declaration).ok()?;
+
+    let (_, value) = declaration.split_once("encoding")?;
+    let value = value.trim_start().strip_prefix('=')?.trim_start();
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let (encoding, _) = value[1..].split_once(quote)?;
+    Some(encoding.to_ascii_lowercase())
+}
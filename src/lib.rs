@@ -1,8 +1,77 @@
 //! Rust serde bindings for Microsoft Windows Autounattend.xml.
+//!
+//! Elements that aren't modelled by a type are kept in its `preserved` field and
+//! written back out, so existing answer files can be modified without losing their
+//! other settings. They keep their order among each other, but are written after the
+//! modelled settings of their parent. Comments are kept there too, anchored to the
+//! element that follows them, and comments before the root element in
+//! [`UnattendXml::prolog`].
+//!
+//! Some content is still dropped when a file is read:
+//! - attributes that aren't modelled, such as `wcm:keyValue` on a `LocalAccount`,
+//!   except on unmodelled elements
+//! - comments within the text of a setting, such as
+//!   `<ComputerName><!-- -->pc</ComputerName>`
+//! - comments after the root element
 #![allow(non_snake_case)]
 
+mod de;
+mod dom;
+mod encoding;
+mod se;
+mod validate;
+
+pub use dom::{Comment, Element, Node, Preserved};
+pub use validate::Diagnostic;
+
 use serde::{Deserialize, Serialize};
-use std::{error::Error, path::Path};
+use std::{
+    fmt,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Errors that can occur while reading or writing an unattend.xml file.
+#[derive(Debug)]
+pub enum Error {
+    /// The file couldn't be read or written.
+    Io(std::io::Error),
+    /// The document couldn't be serialized to XML.
+    Serialize(String),
+    /// The XML couldn't be deserialized into a document.
+    Deserialize(String),
+    /// The document is not a valid answer file.
+    Validation(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "I/O error: {error}"),
+            Error::Serialize(message) => write!(f, "failed to serialize: {message}"),
+            Error::Deserialize(message) => write!(f, "failed to deserialize: {message}"),
+            Error::Validation(message) => write!(f, "invalid answer file: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+const UNATTEND_NAMESPACE: &str = "urn:schemas-microsoft-com:unattend";
 
 /// Represents an unattend.xml file.
 #[derive(Clone, Serialize, Deserialize)]
@@ -10,305 +79,1657 @@ use std::{error::Error, path::Path};
 pub struct UnattendXml {
     #[serde(rename = "@xmlns")]
     pub xmlns: String,
+    /// Packages to install, remove or configure during the offlineServicing pass.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub servicing: Option<Servicing>,
+    #[serde(default)]
     pub settings: Vec<Settings>,
+    /// The Windows image catalog the file was authored against in Windows SIM.
+    #[serde(rename = "cpi:offlineImage", skip_serializing_if = "Option::is_none")]
+    pub offlineImage: Option<OfflineImage>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+    /// Comments before the root element, such as a header describing the file.
+    #[serde(skip)]
+    pub prolog: Vec<String>,
 }
 
 impl UnattendXml {
-    pub fn write_to(&self, path: &Path) -> Result<(), Box<dyn Error>> {
-        std::fs::write(
-            path.join("Autounattend.xml"),
-            quick_xml::se::to_string(&self).unwrap(),
-        )
-        .unwrap();
+    /// Read an unattend.xml file from the given reader.
+    pub fn from_reader(mut reader: impl BufRead) -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_slice(&bytes)
+    }
+
+    /// Read an unattend.xml file from the given path.
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        Self::from_slice(&std::fs::read(path)?)
+    }
+
+    /// Read an unattend.xml file from raw bytes, which may be UTF-8 or UTF-16 as
+    /// indicated by a byte order mark or the XML declaration.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        encoding::decode(bytes)?.parse()
+    }
+
+    /// Serialize to an XML string formatted like Windows SIM output.
+    pub fn to_string(&self) -> Result<String, Error> {
+        self.to_string_with(&WriteOptions::default())
+    }
+
+    /// Serialize to an XML string with the given formatting. The encoding is only
+    /// named in the declaration, since it's applied when the string is written.
+    pub fn to_string_with(&self, options: &WriteOptions) -> Result<String, Error> {
+        self.check_namespace()?;
+        if options.validate {
+            let diagnostics = self.validate();
+            if !diagnostics.is_empty() {
+                let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+                return Err(Error::Validation(messages.join("; ")));
+            }
+        }
+        se::to_string_with(self, &self.prolog, options)
+    }
+
+    /// Serialize to encoded bytes with the given formatting.
+    pub fn to_vec_with(&self, options: &WriteOptions) -> Result<Vec<u8>, Error> {
+        Ok(encoding::encode(
+            &self.to_string_with(options)?,
+            options.encoding,
+        ))
+    }
+
+    /// Serialize as XML into the given writer.
+    pub fn to_writer(&self, writer: impl Write) -> Result<(), Error> {
+        self.to_writer_with(writer, &WriteOptions::default())
+    }
+
+    /// Serialize as XML into the given writer with the given formatting.
+    pub fn to_writer_with(
+        &self,
+        mut writer: impl Write,
+        options: &WriteOptions,
+    ) -> Result<(), Error> {
+        writer.write_all(&self.to_vec_with(options)?)?;
+        Ok(())
+    }
+
+    /// Check for settings that Windows Setup would ignore or reject, such as
+    /// components placed in a pass they don't apply to.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        validate::validate(self)
+    }
+
+    /// Renumber the orders of every list of commands, see [`RunSynchronous::renumber`].
+    pub fn renumber(&mut self) {
+        for component in self
+            .settings
+            .iter_mut()
+            .flat_map(|settings| &mut settings.component)
+        {
+            match component {
+                Component::Setup(Setup {
+                    RunSynchronous: Some(commands),
+                    ..
+                })
+                | Component::Deployment(Deployment {
+                    RunSynchronous: Some(commands),
+                    ..
+                }) => commands.renumber(),
+                Component::ShellSetup(ShellSetup {
+                    FirstLogonCommands: Some(commands),
+                    ..
+                }) => commands.renumber(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Write an Autounattend.xml file into the given directory.
+    pub fn write_to(&self, path: &Path) -> Result<(), Error> {
+        self.write_file(&SearchLocation::MediaRoot.path(path))
+    }
+
+    /// Write to exactly the given file path.
+    pub fn write_file(&self, path: &Path) -> Result<(), Error> {
+        self.write_file_with(path, &WriteOptions::default())
+    }
+
+    /// Write to exactly the given file path with the given formatting.
+    pub fn write_file_with(&self, path: &Path, options: &WriteOptions) -> Result<(), Error> {
+        std::fs::write(path, self.to_vec_with(options)?)?;
+        Ok(())
+    }
+
+    /// Write to one of the locations Windows Setup implicitly searches, relative to
+    /// the given root and creating any missing directories. Returns the path of the
+    /// written file.
+    pub fn write_to_location(
+        &self,
+        root: &Path,
+        location: SearchLocation,
+    ) -> Result<PathBuf, Error> {
+        self.write_to_location_with(root, location, &WriteOptions::default())
+    }
+
+    /// Write to one of the locations Windows Setup implicitly searches with the given
+    /// formatting.
+    pub fn write_to_location_with(
+        &self,
+        root: &Path,
+        location: SearchLocation,
+        options: &WriteOptions,
+    ) -> Result<PathBuf, Error> {
+        let path = location.path(root);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        self.write_file_with(&path, options)?;
+        Ok(path)
+    }
+
+    /// Windows Setup ignores elements outside of the unattend namespace, which
+    /// would be all of them.
+    fn check_namespace(&self) -> Result<(), Error> {
+        if self.xmlns != UNATTEND_NAMESPACE {
+            return Err(Error::Validation(format!(
+                "the namespace must be `{UNATTEND_NAMESPACE}`, not `{}`",
+                self.xmlns
+            )));
+        }
         Ok(())
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(rename = "settings")]
-pub struct Settings {
-    pub component: Vec<Component>,
-    #[serde(rename = "@pass")]
-    pub pass: String,
+/// How an answer file is formatted when it's written. The default matches the files
+/// saved by Windows SIM.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Start with an `<?xml version="1.0" encoding="..."?>` declaration.
+    pub declaration: bool,
+    /// The number of spaces to indent each level by, or `None` to write everything
+    /// on one line.
+    pub indent: Option<usize>,
+    /// The line ending to use between elements.
+    pub line_ending: LineEnding,
+    /// Start with a byte order mark. UTF-16 output always has one.
+    pub bom: bool,
+    /// The encoding of written files.
+    pub encoding: Encoding,
+    /// Where namespace prefixes such as `wcm` are declared.
+    pub namespaces: NamespaceDeclarations,
+    /// Refuse to write files with problems found by [`UnattendXml::validate`],
+    /// returning [`Error::Validation`] instead.
+    pub validate: bool,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(rename = "component")]
-pub struct Component {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ComputerName: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub DiskConfiguration: Option<DiskConfiguration>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub FirstLogonCommands: Option<FirstLogonCommands>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ImageInstall: Option<ImageInstall>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub InputLocale: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub RunSynchronous: Option<RunSynchronous>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub SetupUILanguage: Option<SetupUILanguage>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub SystemLocale: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub UILanguage: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub UILanguageFallback: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub UserAccounts: Option<Vec<LocalAccounts>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub UserData: Option<UserData>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub UserLocale: Option<String>,
-    #[serde(rename = "@language")]
-    pub language: String,
-    #[serde(rename = "@name")]
-    pub name: String,
-    #[serde(rename = "@processorArchitecture")]
-    pub processorArchitecture: String,
-    #[serde(rename = "@publicKeyToken")]
-    pub publicKeyToken: String,
-    #[serde(rename = "@versionScope")]
-    pub versionScope: String,
-    #[serde(rename = "@xmlns:wcm")]
-    pub xmlns_wcm: String,
-    #[serde(rename = "@xmlns:xsi")]
-    pub xmlns_xsi: String,
+impl WriteOptions {
+    /// No declaration and everything on one line.
+    pub fn compact() -> Self {
+        WriteOptions {
+            declaration: false,
+            indent: None,
+            line_ending: LineEnding::Lf,
+            bom: false,
+            encoding: Encoding::Utf8,
+            namespaces: NamespaceDeclarations::PerComponent,
+            validate: false,
+        }
+    }
 }
 
-impl Default for Component {
+impl Default for WriteOptions {
     fn default() -> Self {
-        Component {
-            ComputerName: None,
-            DiskConfiguration: None,
-            FirstLogonCommands: None,
-            ImageInstall: None,
-            InputLocale: None,
-            RunSynchronous: None,
-            SetupUILanguage: None,
-            SystemLocale: None,
-            UILanguage: None,
-            UILanguageFallback: None,
-            UserAccounts: None,
-            UserData: None,
-            UserLocale: None,
-            language: "neutral".into(),
-            name: "".into(),
-            processorArchitecture: "amd64".into(),
-            publicKeyToken: "31bf3856ad364e35".into(),
-            versionScope: "nonSxS".into(),
-            xmlns_wcm: "http://schemas.microsoft.com/WMIConfig/2002/State".into(),
-            xmlns_xsi: "http://www.w3.org/2001/XMLSchema-instance".into(),
+        WriteOptions {
+            declaration: true,
+            indent: Some(4),
+            line_ending: LineEnding::CrLf,
+            bom: false,
+            encoding: Encoding::Utf8,
+            namespaces: NamespaceDeclarations::PerComponent,
+            validate: false,
         }
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct RunSynchronous {
-    pub RunSynchronousCommand: Vec<RunSynchronousCommand>,
+/// The line ending used between elements of a formatted answer file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`, as used by Windows tools.
+    #[default]
+    CrLf,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct RunSynchronousCommand {
-    pub Path: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub Description: Option<String>,
-    pub Order: String,
-    #[serde(rename = "@wcm:action")]
-    pub action: String,
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct SetupUILanguage {
-    pub UILanguage: String,
+/// Where the namespace prefixes of a written answer file are declared.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NamespaceDeclarations {
+    /// On each element that uses them, such as every `component`, as Windows SIM does.
+    #[default]
+    PerComponent,
+    /// Once on the root `unattend` element.
+    Root,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct UserData {
-    pub AcceptEula: String,
-    pub FullName: String,
-    pub Organization: String,
-    pub ProductKey: ProductKey,
+/// The character encoding of a written answer file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// Little-endian UTF-16, as saved by Notepad's "Unicode" option.
+    Utf16,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct ProductKey {
-    pub Key: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub WillShowUI: Option<String>,
+impl Encoding {
+    /// The name used in the XML declaration.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf16 => "utf-16",
+        }
+    }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct FirstLogonCommands {
-    pub SynchronousCommand: Vec<SynchronousCommand>,
+/// A location that Windows Setup implicitly searches for an answer file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchLocation {
+    /// `Autounattend.xml` at the root of the installation media.
+    MediaRoot,
+    /// `sources\Autounattend.xml` on the installation media.
+    Sources,
+    /// `sources\$OEM$\$$\Panther\unattend.xml` on the installation media, which
+    /// Setup copies to `%WINDIR%\Panther`.
+    OemPanther,
+    /// `Windows\Panther\Unattend\unattend.xml` on the Windows volume.
+    WindowsPantherUnattend,
+    /// `Windows\Panther\unattend.xml` on the Windows volume.
+    WindowsPanther,
+    /// `Windows\System32\Sysprep\unattend.xml` on the Windows volume.
+    Sysprep,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct SynchronousCommand {
-    pub CommandLine: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub Description: Option<String>,
-    pub Order: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub RequiresUserInput: Option<String>,
-    #[serde(rename = "@wcm:action", skip_serializing_if = "Option::is_none")]
-    pub action: Option<String>,
+impl SearchLocation {
+    /// The path of the answer file relative to the given root.
+    pub fn path(&self, root: &Path) -> PathBuf {
+        let components: &[&str] = match self {
+            SearchLocation::MediaRoot => &["Autounattend.xml"],
+            SearchLocation::Sources => &["sources", "Autounattend.xml"],
+            SearchLocation::OemPanther => &["sources", "$OEM$", "$$", "Panther", "unattend.xml"],
+            SearchLocation::WindowsPantherUnattend => {
+                &["Windows", "Panther", "Unattend", "unattend.xml"]
+            }
+            SearchLocation::WindowsPanther => &["Windows", "Panther", "unattend.xml"],
+            SearchLocation::Sysprep => &["Windows", "System32", "Sysprep", "unattend.xml"],
+        };
+        components
+            .iter()
+            .fold(root.to_path_buf(), |path, component| path.join(component))
+    }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct LocalAccounts {
-    // pub LocalAccounts: Vec<LocalAccount>,
+impl FromStr for UnattendXml {
+    type Err = Error;
+
+    /// Read an unattend.xml file from a string.
+    fn from_str(xml: &str) -> Result<Self, Self::Err> {
+        let (prolog, root) = Element::parse_document(xml)?;
+        Ok(UnattendXml {
+            prolog,
+            ..de::from_element(&root)?
+        })
+    }
 }
 
+/// The Windows image that Windows SIM associates with an answer file.
 #[derive(Clone, Serialize, Deserialize)]
-pub struct LocalAccount {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub Description: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub DisplayName: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub Group: Option<String>,
-    pub Name: String,
-    pub Password: Password,
-    #[serde(rename = "@wcm:action")]
-    pub action: String,
+pub struct OfflineImage {
+    /// The path to the image or its catalog, e.g. `catalog://server/share/install.clg`.
+    #[serde(rename = "@cpi:source")]
+    pub source: String,
+    #[serde(rename = "@xmlns:cpi", default = "cpi_namespace")]
+    pub xmlns_cpi: String,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct Password {
-    pub PlainText: String,
-    pub Value: String,
+impl OfflineImage {
+    pub fn new(source: impl Into<String>) -> Self {
+        OfflineImage {
+            source: source.into(),
+            xmlns_cpi: cpi_namespace(),
+            preserved: Preserved::default(),
+        }
+    }
+}
+
+/// Packages applied to the Windows image offline.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "servicing")]
+pub struct Servicing {
+    #[serde(default)]
+    pub package: Vec<Package>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
 }
 
+/// A package such as a language pack, update or the foundation package whose
+/// optional features are selected.
 #[derive(Clone, Serialize, Deserialize)]
-pub struct DiskConfiguration {
-    pub Disk: Disk,
+pub struct Package {
+    pub assemblyIdentity: AssemblyIdentity,
+    /// Where to find the package when installing it.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub WillShowUI: Option<String>,
+    pub source: Option<PackageSource>,
+    /// The features to enable or disable when configuring the package.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub selection: Vec<Selection>,
+    #[serde(rename = "@action")]
+    pub action: PackageAction,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct Disk {
-    pub CreatePartitions: CreatePartitions,
-    pub DiskID: String,
-    pub ModifyPartitions: ModifyPartitions,
-    pub WillWipeDisk: String,
-    #[serde(rename = "@wcm:action")]
-    pub action: String,
+impl Package {
+    /// Install the package from the given location.
+    pub fn install(assemblyIdentity: AssemblyIdentity, location: impl Into<String>) -> Self {
+        Package {
+            source: Some(PackageSource {
+                location: location.into(),
+                preserved: Preserved::default(),
+            }),
+            ..Package::new(PackageAction::Install, assemblyIdentity)
+        }
+    }
+
+    /// Remove the package from the image.
+    pub fn remove(assemblyIdentity: AssemblyIdentity) -> Self {
+        Package::new(PackageAction::Remove, assemblyIdentity)
+    }
+
+    /// Enable or disable features of the package.
+    pub fn configure(assemblyIdentity: AssemblyIdentity, selection: Vec<Selection>) -> Self {
+        Package {
+            selection,
+            ..Package::new(PackageAction::Configure, assemblyIdentity)
+        }
+    }
+
+    fn new(action: PackageAction, assemblyIdentity: AssemblyIdentity) -> Self {
+        Package {
+            assemblyIdentity,
+            source: None,
+            selection: Vec::new(),
+            action,
+            preserved: Preserved::default(),
+        }
+    }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct CreatePartitions {
-    pub CreatePartition: Vec<CreatePartition>,
+/// What to do with a servicing package.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageAction {
+    Install,
+    Remove,
+    Configure,
 }
 
+/// Identifies a package by its name, version and target platform.
 #[derive(Clone, Serialize, Deserialize)]
-pub struct CreatePartition {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub Extend: Option<String>,
-    pub Order: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub Size: Option<String>,
-    pub Type: String,
-    #[serde(rename = "@wcm:action")]
-    pub action: String,
+pub struct AssemblyIdentity {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@version")]
+    pub version: String,
+    #[serde(rename = "@processorArchitecture")]
+    pub processorArchitecture: String,
+    #[serde(rename = "@publicKeyToken")]
+    pub publicKeyToken: String,
+    #[serde(rename = "@language", default)]
+    pub language: String,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct ModifyPartitions {
-    pub ModifyPartition: Vec<ModifyPartition>,
+impl AssemblyIdentity {
+    /// A language-neutral amd64 package signed by Microsoft.
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        AssemblyIdentity {
+            name: name.into(),
+            version: version.into(),
+            processorArchitecture: "amd64".into(),
+            publicKeyToken: "31bf3856ad364e35".into(),
+            language: "neutral".into(),
+            preserved: Preserved::default(),
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
-pub struct ModifyPartition {
-    pub Format: String,
-    pub Label: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub Letter: Option<String>,
-    pub Order: String,
-    pub PartitionID: String,
-    #[serde(rename = "@wcm:action")]
-    pub action: String,
+pub struct PackageSource {
+    /// The path to the `.cab` file or its folder.
+    #[serde(rename = "@location")]
+    pub location: String,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
 }
 
+/// Enables or disables one feature of a package.
 #[derive(Clone, Serialize, Deserialize)]
-pub struct ImageInstall {
-    pub OSImage: OSImage,
+pub struct Selection {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@state", with = "boolean")]
+    pub state: bool,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct OSImage {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub InstallTo: Option<InstallTo>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub InstallToAvailablePartition: Option<String>,
-    // pub InstallFrom: Option<InstallFrom>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub WillShowUI: Option<String>,
+impl Selection {
+    pub fn new(name: impl Into<String>, state: bool) -> Self {
+        Selection {
+            name: name.into(),
+            state,
+            preserved: Preserved::default(),
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
-pub struct InstallTo {
-    pub DiskID: String,
-    pub PartitionID: String,
+#[serde(rename = "settings")]
+pub struct Settings {
+    #[serde(default)]
+    pub component: Vec<Component>,
+    #[serde(rename = "@pass")]
+    pub pass: Pass,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// A configuration pass of Windows Setup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Pass {
+    WindowsPE,
+    OfflineServicing,
+    Generalize,
+    Specialize,
+    AuditSystem,
+    AuditUser,
+    OobeSystem,
+}
 
-    use std::io::{Cursor, Write};
-    use xml::reader::{EventReader, XmlEvent};
-    use xml::writer::{EmitterConfig, EventWriter};
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The camelCase name given by serde only lowercases the first letter
+        let name = format!("{self:?}");
+        let (first, rest) = name.split_at(1);
+        write!(f, "{}{rest}", first.to_lowercase())
+    }
+}
 
-    fn unformat(xml: &str) -> String {
-        let parser = EventReader::new(Cursor::new(xml));
-        let mut result = Vec::new();
-        let mut writer = EmitterConfig::new()
-            .perform_indent(false)
-            .write_document_declaration(false)
-            .create_writer(&mut result);
+/// A component within a configuration pass, identified by its `name` attribute.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "Element", into = "RawComponent")]
+#[allow(clippy::large_enum_variant)]
+pub enum Component {
+    InternationalCoreWinPE(InternationalCoreWinPE),
+    InternationalCore(InternationalCore),
+    Setup(Setup),
+    ShellSetup(ShellSetup),
+    Deployment(Deployment),
+    PnpCustomizationsWinPE(PnpCustomizationsWinPE),
+    PnpCustomizationsNonWinPE(PnpCustomizationsNonWinPE),
+    /// A component that isn't modelled by this crate.
+    Other(OtherComponent),
+}
 
-        for event in parser {
-            match event {
-                Ok(XmlEvent::Characters(ref chars)) if chars.trim().is_empty() => {}
-                Ok(event) => {
-                    writer.write(event).unwrap();
-                }
-                Err(e) => panic!("Error parsing XML: {:?}", e),
-            }
+impl Component {
+    /// The value of the `name` attribute for this component.
+    pub fn name(&self) -> &str {
+        match self {
+            Component::InternationalCoreWinPE(_) => InternationalCoreWinPE::NAME,
+            Component::InternationalCore(_) => InternationalCore::NAME,
+            Component::Setup(_) => Setup::NAME,
+            Component::ShellSetup(_) => ShellSetup::NAME,
+            Component::Deployment(_) => Deployment::NAME,
+            Component::PnpCustomizationsWinPE(_) => PnpCustomizationsWinPE::NAME,
+            Component::PnpCustomizationsNonWinPE(_) => PnpCustomizationsNonWinPE::NAME,
+            Component::Other(component) => &component.name,
         }
+    }
 
-        String::from_utf8(result).expect("Invalid UTF-8 in XML string")
+    pub fn attributes(&self) -> &ComponentAttributes {
+        match self {
+            Component::InternationalCoreWinPE(component) => &component.attributes,
+            Component::InternationalCore(component) => &component.attributes,
+            Component::Setup(component) => &component.attributes,
+            Component::ShellSetup(component) => &component.attributes,
+            Component::Deployment(component) => &component.attributes,
+            Component::PnpCustomizationsWinPE(component) => &component.attributes,
+            Component::PnpCustomizationsNonWinPE(component) => &component.attributes,
+            Component::Other(component) => &component.attributes,
+        }
     }
 
-    #[test]
-    fn test_serialize_1() {
-        let raw_xml = r#"
-            <?xml version="1.0" encoding="utf-8"?>
-            <unattend xmlns="urn:schemas-microsoft-com:unattend">
-            </unattend>
-        "#;
+    /// Content of the component that isn't modelled, including settings which
+    /// belong to a different component.
+    pub fn preserved(&self) -> &Preserved {
+        match self {
+            Component::InternationalCoreWinPE(component) => &component.preserved,
+            Component::InternationalCore(component) => &component.preserved,
+            Component::Setup(component) => &component.preserved,
+            Component::ShellSetup(component) => &component.preserved,
+            Component::Deployment(component) => &component.preserved,
+            Component::PnpCustomizationsWinPE(component) => &component.preserved,
+            Component::PnpCustomizationsNonWinPE(component) => &component.preserved,
+            Component::Other(component) => &component.preserved,
+        }
+    }
+}
 
-        let unattended = UnattendXml {
-            xmlns: "urn:schemas-microsoft-com:unattend".into(),
-            settings: vec![Settings {
-                component: vec![Component {
-                    ..Default::default()
-                }],
-                pass: "windowsPE".into(),
-            }],
+impl Component {
+    /// The modelled settings of every component, whichever component they belong to.
+    const SETTINGS: &'static [&'static str] = &[
+        "AutoLogon",
+        "ComputerName",
+        "DiskConfiguration",
+        "DriverPaths",
+        "FirstLogonCommands",
+        "ImageInstall",
+        "InputLocale",
+        "OOBE",
+        "RunSynchronous",
+        "SetupUILanguage",
+        "SystemLocale",
+        "UILanguage",
+        "UILanguageFallback",
+        "UserAccounts",
+        "UserData",
+        "UserLocale",
+    ];
+
+    /// Settings of a different component which were kept with the unknown elements.
+    pub(crate) fn misplaced_settings(&self) -> impl Iterator<Item = &str> {
+        let preserved = match self {
+            Component::Other(_) => None,
+            component => Some(component.preserved()),
         };
-        assert_eq!(
-            unformat(raw_xml),
-            quick_xml::se::to_string(&unattended).unwrap()
+        preserved
+            .into_iter()
+            .flat_map(|preserved| &preserved.unknown)
+            .map(|element| element.name.as_str())
+            .filter(|name| Component::SETTINGS.contains(name))
+    }
+
+    fn preserved_mut(&mut self) -> &mut Preserved {
+        match self {
+            Component::InternationalCoreWinPE(component) => &mut component.preserved,
+            Component::InternationalCore(component) => &mut component.preserved,
+            Component::Setup(component) => &mut component.preserved,
+            Component::ShellSetup(component) => &mut component.preserved,
+            Component::Deployment(component) => &mut component.preserved,
+            Component::PnpCustomizationsWinPE(component) => &mut component.preserved,
+            Component::PnpCustomizationsNonWinPE(component) => &mut component.preserved,
+            Component::Other(component) => &mut component.preserved,
+        }
+    }
+}
+
+/// The attributes shared by every component besides its name.
+#[derive(Clone)]
+pub struct ComponentAttributes {
+    pub processorArchitecture: String,
+    pub publicKeyToken: String,
+    pub language: String,
+    pub versionScope: String,
+    pub xmlns_wcm: String,
+    pub xmlns_xsi: String,
+}
+
+impl Default for ComponentAttributes {
+    fn default() -> Self {
+        ComponentAttributes {
+            processorArchitecture: "amd64".into(),
+            publicKeyToken: "31bf3856ad364e35".into(),
+            language: "neutral".into(),
+            versionScope: "nonSxS".into(),
+            xmlns_wcm: wcm_namespace(),
+            xmlns_xsi: xsi_namespace(),
+        }
+    }
+}
+
+fn wcm_namespace() -> String {
+    dom::WCM_NAMESPACE.into()
+}
+
+fn xsi_namespace() -> String {
+    dom::XSI_NAMESPACE.into()
+}
+
+fn cpi_namespace() -> String {
+    dom::CPI_NAMESPACE.into()
+}
+
+/// Microsoft-Windows-International-Core-WinPE
+#[derive(Clone, Default)]
+pub struct InternationalCoreWinPE {
+    pub attributes: ComponentAttributes,
+    pub InputLocale: Option<String>,
+    pub SetupUILanguage: Option<SetupUILanguage>,
+    pub SystemLocale: Option<String>,
+    pub UILanguage: Option<String>,
+    pub UILanguageFallback: Option<String>,
+    pub UserLocale: Option<String>,
+    pub preserved: Preserved,
+}
+
+impl InternationalCoreWinPE {
+    pub const NAME: &'static str = "Microsoft-Windows-International-Core-WinPE";
+}
+
+/// Microsoft-Windows-International-Core
+#[derive(Clone, Default)]
+pub struct InternationalCore {
+    pub attributes: ComponentAttributes,
+    pub InputLocale: Option<String>,
+    pub SystemLocale: Option<String>,
+    pub UILanguage: Option<String>,
+    pub UILanguageFallback: Option<String>,
+    pub UserLocale: Option<String>,
+    pub preserved: Preserved,
+}
+
+impl InternationalCore {
+    pub const NAME: &'static str = "Microsoft-Windows-International-Core";
+}
+
+/// Microsoft-Windows-Setup
+#[derive(Clone, Default)]
+pub struct Setup {
+    pub attributes: ComponentAttributes,
+    pub DiskConfiguration: Option<DiskConfiguration>,
+    pub ImageInstall: Option<ImageInstall>,
+    pub RunSynchronous: Option<RunSynchronous>,
+    pub UserData: Option<UserData>,
+    pub preserved: Preserved,
+}
+
+impl Setup {
+    pub const NAME: &'static str = "Microsoft-Windows-Setup";
+}
+
+/// Microsoft-Windows-Shell-Setup
+#[derive(Clone, Default)]
+pub struct ShellSetup {
+    pub attributes: ComponentAttributes,
+    pub AutoLogon: Option<AutoLogon>,
+    pub ComputerName: Option<String>,
+    pub FirstLogonCommands: Option<FirstLogonCommands>,
+    pub OOBE: Option<OOBE>,
+    pub UserAccounts: Option<UserAccounts>,
+    pub preserved: Preserved,
+}
+
+impl ShellSetup {
+    pub const NAME: &'static str = "Microsoft-Windows-Shell-Setup";
+}
+
+/// Microsoft-Windows-Deployment
+#[derive(Clone, Default)]
+pub struct Deployment {
+    pub attributes: ComponentAttributes,
+    pub RunSynchronous: Option<RunSynchronous>,
+    pub preserved: Preserved,
+}
+
+impl Deployment {
+    pub const NAME: &'static str = "Microsoft-Windows-Deployment";
+}
+
+/// Microsoft-Windows-PnpCustomizationsWinPE
+#[derive(Clone, Default)]
+pub struct PnpCustomizationsWinPE {
+    pub attributes: ComponentAttributes,
+    pub DriverPaths: Option<DriverPaths>,
+    pub preserved: Preserved,
+}
+
+impl PnpCustomizationsWinPE {
+    pub const NAME: &'static str = "Microsoft-Windows-PnpCustomizationsWinPE";
+}
+
+/// Microsoft-Windows-PnpCustomizationsNonWinPE
+#[derive(Clone, Default)]
+pub struct PnpCustomizationsNonWinPE {
+    pub attributes: ComponentAttributes,
+    pub DriverPaths: Option<DriverPaths>,
+    pub preserved: Preserved,
+}
+
+impl PnpCustomizationsNonWinPE {
+    pub const NAME: &'static str = "Microsoft-Windows-PnpCustomizationsNonWinPE";
+}
+
+/// A component identified only by its name.
+#[derive(Clone, Default)]
+pub struct OtherComponent {
+    pub name: String,
+    pub attributes: ComponentAttributes,
+    pub preserved: Preserved,
+}
+
+/// The on-disk shape of every component, used to dispatch on the `name` attribute.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename = "component")]
+struct RawComponent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    AutoLogon: Option<AutoLogon>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ComputerName: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    DiskConfiguration: Option<DiskConfiguration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    DriverPaths: Option<DriverPaths>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    FirstLogonCommands: Option<FirstLogonCommands>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ImageInstall: Option<ImageInstall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    InputLocale: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    OOBE: Option<OOBE>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    RunSynchronous: Option<RunSynchronous>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    SetupUILanguage: Option<SetupUILanguage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    SystemLocale: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    UILanguage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    UILanguageFallback: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    UserAccounts: Option<UserAccounts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    UserData: Option<UserData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    UserLocale: Option<String>,
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(rename = "@processorArchitecture")]
+    processorArchitecture: String,
+    #[serde(rename = "@publicKeyToken")]
+    publicKeyToken: String,
+    #[serde(rename = "@language")]
+    language: String,
+    #[serde(rename = "@versionScope")]
+    versionScope: String,
+    // Declarations may instead be inherited from the root element
+    #[serde(rename = "@xmlns:wcm", default = "wcm_namespace")]
+    xmlns_wcm: String,
+    #[serde(rename = "@xmlns:xsi", default = "xsi_namespace")]
+    xmlns_xsi: String,
+    #[serde(rename = "$preserved", default)]
+    preserved: Preserved,
+}
+
+impl RawComponent {
+    fn new(name: &str, attributes: ComponentAttributes, preserved: Preserved) -> Self {
+        RawComponent {
+            name: name.into(),
+            processorArchitecture: attributes.processorArchitecture,
+            publicKeyToken: attributes.publicKeyToken,
+            language: attributes.language,
+            versionScope: attributes.versionScope,
+            xmlns_wcm: attributes.xmlns_wcm,
+            xmlns_xsi: attributes.xmlns_xsi,
+            preserved,
+            ..Default::default()
+        }
+    }
+
+    fn from_element(element: &Element) -> Result<Self, String> {
+        de::from_element(element).map_err(|error| match error {
+            Error::Deserialize(message) => message,
+            error => error.to_string(),
+        })
+    }
+
+    /// Split off the shared attributes, leaving only the settings behind.
+    fn take_attributes(&mut self) -> ComponentAttributes {
+        ComponentAttributes {
+            processorArchitecture: std::mem::take(&mut self.processorArchitecture),
+            publicKeyToken: std::mem::take(&mut self.publicKeyToken),
+            language: std::mem::take(&mut self.language),
+            versionScope: std::mem::take(&mut self.versionScope),
+            xmlns_wcm: std::mem::take(&mut self.xmlns_wcm),
+            xmlns_xsi: std::mem::take(&mut self.xmlns_xsi),
+        }
+    }
+
+    /// The names of any settings that are still present.
+    fn settings(&self) -> Vec<&'static str> {
+        [
+            ("AutoLogon", self.AutoLogon.is_some()),
+            ("ComputerName", self.ComputerName.is_some()),
+            ("DiskConfiguration", self.DiskConfiguration.is_some()),
+            ("DriverPaths", self.DriverPaths.is_some()),
+            ("FirstLogonCommands", self.FirstLogonCommands.is_some()),
+            ("ImageInstall", self.ImageInstall.is_some()),
+            ("InputLocale", self.InputLocale.is_some()),
+            ("OOBE", self.OOBE.is_some()),
+            ("RunSynchronous", self.RunSynchronous.is_some()),
+            ("SetupUILanguage", self.SetupUILanguage.is_some()),
+            ("SystemLocale", self.SystemLocale.is_some()),
+            ("UILanguage", self.UILanguage.is_some()),
+            ("UILanguageFallback", self.UILanguageFallback.is_some()),
+            ("UserAccounts", self.UserAccounts.is_some()),
+            ("UserData", self.UserData.is_some()),
+            ("UserLocale", self.UserLocale.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, present)| present.then_some(name))
+        .collect()
+    }
+
+    /// Move the settings into the component named by the `name` attribute.
+    fn into_component(mut self, element: &Element) -> Component {
+        let attributes = self.take_attributes();
+        let mut component = match self.name.as_str() {
+            InternationalCoreWinPE::NAME => {
+                Component::InternationalCoreWinPE(InternationalCoreWinPE {
+                    attributes,
+                    preserved: Preserved::default(),
+                    InputLocale: self.InputLocale.take(),
+                    SetupUILanguage: self.SetupUILanguage.take(),
+                    SystemLocale: self.SystemLocale.take(),
+                    UILanguage: self.UILanguage.take(),
+                    UILanguageFallback: self.UILanguageFallback.take(),
+                    UserLocale: self.UserLocale.take(),
+                })
+            }
+            InternationalCore::NAME => Component::InternationalCore(InternationalCore {
+                attributes,
+                preserved: Preserved::default(),
+                InputLocale: self.InputLocale.take(),
+                SystemLocale: self.SystemLocale.take(),
+                UILanguage: self.UILanguage.take(),
+                UILanguageFallback: self.UILanguageFallback.take(),
+                UserLocale: self.UserLocale.take(),
+            }),
+            Setup::NAME => Component::Setup(Setup {
+                attributes,
+                preserved: Preserved::default(),
+                DiskConfiguration: self.DiskConfiguration.take(),
+                ImageInstall: self.ImageInstall.take(),
+                RunSynchronous: self.RunSynchronous.take(),
+                UserData: self.UserData.take(),
+            }),
+            ShellSetup::NAME => Component::ShellSetup(ShellSetup {
+                attributes,
+                preserved: Preserved::default(),
+                AutoLogon: self.AutoLogon.take(),
+                ComputerName: self.ComputerName.take(),
+                FirstLogonCommands: self.FirstLogonCommands.take(),
+                OOBE: self.OOBE.take(),
+                UserAccounts: self.UserAccounts.take(),
+            }),
+            Deployment::NAME => Component::Deployment(Deployment {
+                attributes,
+                preserved: Preserved::default(),
+                RunSynchronous: self.RunSynchronous.take(),
+            }),
+            PnpCustomizationsWinPE::NAME => {
+                Component::PnpCustomizationsWinPE(PnpCustomizationsWinPE {
+                    attributes,
+                    preserved: Preserved::default(),
+                    DriverPaths: self.DriverPaths.take(),
+                })
+            }
+            PnpCustomizationsNonWinPE::NAME => {
+                Component::PnpCustomizationsNonWinPE(PnpCustomizationsNonWinPE {
+                    attributes,
+                    preserved: Preserved::default(),
+                    DriverPaths: self.DriverPaths.take(),
+                })
+            }
+            _ => Component::Other(OtherComponent {
+                name: self.name.clone(),
+                attributes,
+                preserved: Preserved::default(),
+            }),
+        };
+
+        // Anything left over belongs to a different component, and is kept with the
+        // unknown elements in the order it was read
+        let misplaced = self.settings();
+        component.preserved_mut().comments = std::mem::take(&mut self.preserved.comments);
+        component.preserved_mut().unknown = element
+            .elements()
+            .filter(|child| {
+                misplaced.contains(&child.name.as_str())
+                    || self
+                        .preserved
+                        .unknown
+                        .iter()
+                        .any(|unknown| unknown.name == child.name)
+            })
+            .cloned()
+            .collect();
+        component
+    }
+}
+
+impl From<Component> for RawComponent {
+    fn from(component: Component) -> Self {
+        match component {
+            Component::InternationalCoreWinPE(component) => RawComponent {
+                InputLocale: component.InputLocale,
+                SetupUILanguage: component.SetupUILanguage,
+                SystemLocale: component.SystemLocale,
+                UILanguage: component.UILanguage,
+                UILanguageFallback: component.UILanguageFallback,
+                UserLocale: component.UserLocale,
+                ..RawComponent::new(
+                    InternationalCoreWinPE::NAME,
+                    component.attributes,
+                    component.preserved,
+                )
+            },
+            Component::InternationalCore(component) => RawComponent {
+                InputLocale: component.InputLocale,
+                SystemLocale: component.SystemLocale,
+                UILanguage: component.UILanguage,
+                UILanguageFallback: component.UILanguageFallback,
+                UserLocale: component.UserLocale,
+                ..RawComponent::new(
+                    InternationalCore::NAME,
+                    component.attributes,
+                    component.preserved,
+                )
+            },
+            Component::Setup(component) => RawComponent {
+                DiskConfiguration: component.DiskConfiguration,
+                ImageInstall: component.ImageInstall,
+                RunSynchronous: component.RunSynchronous,
+                UserData: component.UserData,
+                ..RawComponent::new(Setup::NAME, component.attributes, component.preserved)
+            },
+            Component::ShellSetup(component) => RawComponent {
+                AutoLogon: component.AutoLogon,
+                ComputerName: component.ComputerName,
+                FirstLogonCommands: component.FirstLogonCommands,
+                OOBE: component.OOBE,
+                UserAccounts: component.UserAccounts,
+                ..RawComponent::new(ShellSetup::NAME, component.attributes, component.preserved)
+            },
+            Component::Deployment(component) => RawComponent {
+                RunSynchronous: component.RunSynchronous,
+                ..RawComponent::new(Deployment::NAME, component.attributes, component.preserved)
+            },
+            Component::PnpCustomizationsWinPE(component) => RawComponent {
+                DriverPaths: component.DriverPaths,
+                ..RawComponent::new(
+                    PnpCustomizationsWinPE::NAME,
+                    component.attributes,
+                    component.preserved,
+                )
+            },
+            Component::PnpCustomizationsNonWinPE(component) => RawComponent {
+                DriverPaths: component.DriverPaths,
+                ..RawComponent::new(
+                    PnpCustomizationsNonWinPE::NAME,
+                    component.attributes,
+                    component.preserved,
+                )
+            },
+            Component::Other(component) => {
+                RawComponent::new(&component.name, component.attributes, component.preserved)
+            }
+        }
+    }
+}
+
+impl TryFrom<Element> for Component {
+    type Error = String;
+
+    fn try_from(element: Element) -> Result<Self, Self::Error> {
+        let modelled = [
+            InternationalCoreWinPE::NAME,
+            InternationalCore::NAME,
+            Setup::NAME,
+            ShellSetup::NAME,
+            Deployment::NAME,
+            PnpCustomizationsWinPE::NAME,
+            PnpCustomizationsNonWinPE::NAME,
+        ];
+        if modelled.contains(&element.attribute("name").unwrap_or_default()) {
+            return Ok(RawComponent::from_element(&element)?.into_component(&element));
+        }
+
+        // The settings of other components are kept as they are, even those that
+        // share a name with a modelled setting
+        let mut raw = RawComponent::from_element(&Element {
+            name: element.name.clone(),
+            attributes: element.attributes.clone(),
+            children: Vec::new(),
+        })?;
+        Ok(Component::Other(OtherComponent {
+            name: std::mem::take(&mut raw.name),
+            attributes: raw.take_attributes(),
+            preserved: Preserved {
+                unknown: element.elements().cloned().collect(),
+                comments: element.comments(),
+            },
+        }))
+    }
+}
+
+/// How a list item is merged with the same item in an earlier answer file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    #[default]
+    Add,
+    Modify,
+    Remove,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RunSynchronous {
+    pub RunSynchronousCommand: Vec<RunSynchronousCommand>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+impl RunSynchronous {
+    /// Reassign every command's order sequentially from 1, keeping their relative
+    /// order. Commands with the same order keep the order they appear in.
+    pub fn renumber(&mut self) {
+        renumber(
+            self.RunSynchronousCommand
+                .iter_mut()
+                .map(|command| &mut command.Order),
         );
     }
+}
 
-    #[test]
-    fn test_deserialize_1() {
-        let raw_xml = r#"
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RunSynchronousCommand {
+    pub Path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Description: Option<String>,
+    pub Order: u32,
+    #[serde(rename = "@wcm:action", default)]
+    pub action: Action,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SetupUILanguage {
+    pub UILanguage: String,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UserData {
+    #[serde(with = "boolean")]
+    pub AcceptEula: bool,
+    pub FullName: String,
+    pub Organization: String,
+    pub ProductKey: ProductKey,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProductKey {
+    pub Key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub WillShowUI: Option<String>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AutoLogon {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Domain: Option<String>,
+    #[serde(with = "boolean")]
+    pub Enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub LogonCount: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Password: Option<Password>,
+    pub Username: String,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FirstLogonCommands {
+    pub SynchronousCommand: Vec<SynchronousCommand>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+impl FirstLogonCommands {
+    /// Reassign every command's order sequentially from 1, keeping their relative
+    /// order. Commands with the same order keep the order they appear in.
+    pub fn renumber(&mut self) {
+        renumber(
+            self.SynchronousCommand
+                .iter_mut()
+                .map(|command| &mut command.Order),
+        );
+    }
+}
+
+/// The largest order Windows Setup accepts for a command.
+pub const MAX_COMMAND_ORDER: u32 = 500;
+
+/// Number the given orders from 1 without changing where the commands are, so that
+/// any comments stay with their command.
+fn renumber<'a>(orders: impl Iterator<Item = &'a mut u32>) {
+    let mut orders: Vec<&mut u32> = orders.collect();
+    orders.sort_by_key(|order| **order);
+    for (order, renumbered) in orders.into_iter().zip(1..) {
+        *order = renumbered;
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SynchronousCommand {
+    pub CommandLine: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Description: Option<String>,
+    pub Order: u32,
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub RequiresUserInput: Option<bool>,
+    #[serde(rename = "@wcm:action", default)]
+    pub action: Action,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+/// Settings for the Windows Welcome (OOBE) screens.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct OOBE {
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub HideEULAPage: Option<bool>,
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub HideLocalAccountScreen: Option<bool>,
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub HideOEMRegistrationScreen: Option<bool>,
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub HideOnlineAccountScreens: Option<bool>,
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub HideWirelessSetupInOOBE: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub NetworkLocation: Option<NetworkLocation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ProtectYourPC: Option<ProtectYourPC>,
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub SkipMachineOOBE: Option<bool>,
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub SkipUserOOBE: Option<bool>,
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub UnattendEnableRetailDemo: Option<bool>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+/// The location of the network the computer is connected to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetworkLocation {
+    Home,
+    Work,
+    Other,
+}
+
+/// The level of automatic protection applied during OOBE.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProtectYourPC {
+    /// Use recommended settings.
+    #[serde(rename = "1")]
+    Recommended,
+    /// Only install security updates.
+    #[serde(rename = "2")]
+    UpdatesOnly,
+    /// Disable automatic protection.
+    #[serde(rename = "3")]
+    Disabled,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct UserAccounts {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub AdministratorPassword: Option<Password>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub DomainAccounts: Option<DomainAccounts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub LocalAccounts: Option<LocalAccounts>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LocalAccounts {
+    pub LocalAccount: Vec<LocalAccount>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LocalAccount {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub DisplayName: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Group: Option<String>,
+    pub Name: String,
+    pub Password: Password,
+    #[serde(rename = "@wcm:action", default)]
+    pub action: Action,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DomainAccounts {
+    pub DomainAccountList: Vec<DomainAccountList>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DomainAccountList {
+    pub Domain: String,
+    pub DomainAccount: Vec<DomainAccount>,
+    #[serde(rename = "@wcm:action", default)]
+    pub action: Action,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DomainAccount {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Group: Option<String>,
+    pub Name: String,
+    #[serde(rename = "@wcm:action", default)]
+    pub action: Action,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Password {
+    #[serde(with = "boolean")]
+    pub PlainText: bool,
+    pub Value: String,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+/// Paths to search for device drivers.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DriverPaths {
+    pub PathAndCredentials: Vec<PathAndCredentials>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PathAndCredentials {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Credentials: Option<Credentials>,
+    pub Path: String,
+    #[serde(rename = "@wcm:keyValue")]
+    pub keyValue: String,
+    #[serde(rename = "@wcm:action", default)]
+    pub action: Action,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DiskConfiguration {
+    /// The disks to configure, each identified by its `DiskID`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub Disk: Vec<Disk>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub WillShowUI: Option<String>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+impl DiskConfiguration {
+    /// The configuration of the disk with the given ID.
+    pub fn disk(&self, id: u32) -> Option<&Disk> {
+        self.Disk.iter().find(|disk| disk.DiskID == id)
+    }
+
+    /// The configuration of the disk with the given ID, for modification.
+    pub fn disk_mut(&mut self, id: u32) -> Option<&mut Disk> {
+        self.Disk.iter_mut().find(|disk| disk.DiskID == id)
+    }
+
+    /// Add or replace the configuration of the disk with the same ID.
+    pub fn set_disk(&mut self, disk: Disk) {
+        match self.disk_mut(disk.DiskID) {
+            Some(existing) => *existing = disk,
+            None => self.Disk.push(disk),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Disk {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub CreatePartitions: Option<CreatePartitions>,
+    pub DiskID: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ModifyPartitions: Option<ModifyPartitions>,
+    #[serde(with = "boolean")]
+    pub WillWipeDisk: bool,
+    #[serde(rename = "@wcm:action", default)]
+    pub action: Action,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CreatePartitions {
+    #[serde(default)]
+    pub CreatePartition: Vec<CreatePartition>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CreatePartition {
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Extend: Option<bool>,
+    pub Order: u32,
+    /// The size in MB. Leave unset when `Extend` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Size: Option<u64>,
+    pub Type: PartitionType,
+    #[serde(rename = "@wcm:action", default)]
+    pub action: Action,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+/// The kind of partition to create.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PartitionType {
+    Primary,
+    /// An EFI system partition.
+    EFI,
+    /// A Microsoft reserved partition.
+    MSR,
+    Extended,
+    Logical,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ModifyPartitions {
+    #[serde(default)]
+    pub ModifyPartition: Vec<ModifyPartition>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ModifyPartition {
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Active: Option<bool>,
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Extend: Option<bool>,
+    /// The file system, such as `NTFS` or `FAT32`. MSR partitions have none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Letter: Option<String>,
+    pub Order: u32,
+    pub PartitionID: u32,
+    /// The partition type, as an MBR type such as `0x27` or a GPT type GUID.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub TypeID: Option<String>,
+    #[serde(rename = "@wcm:action", default)]
+    pub action: Action,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ImageInstall {
+    pub OSImage: OSImage,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OSImage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub InstallFrom: Option<InstallFrom>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub InstallTo: Option<InstallTo>,
+    #[serde(default, with = "boolean::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub InstallToAvailablePartition: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub WillShowUI: Option<String>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+/// Where to install the image from and which image to select.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct InstallFrom {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Credentials: Option<Credentials>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub MetaData: Vec<MetaData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Path: Option<String>,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+impl InstallFrom {
+    /// Select the image with the given index in the image file.
+    pub fn by_index(index: u32) -> Self {
+        Self::by_metadata("/IMAGE/INDEX", index.to_string())
+    }
+
+    /// Select the image with the given name, e.g. "Windows 11 Pro".
+    pub fn by_name(name: impl Into<String>) -> Self {
+        Self::by_metadata("/IMAGE/NAME", name)
+    }
+
+    /// Select the image with the given description.
+    pub fn by_description(description: impl Into<String>) -> Self {
+        Self::by_metadata("/IMAGE/DESCRIPTION", description)
+    }
+
+    fn by_metadata(key: &str, value: impl Into<String>) -> Self {
+        InstallFrom {
+            MetaData: vec![MetaData {
+                Key: key.into(),
+                Value: value.into(),
+                action: Action::Add,
+                preserved: Preserved::default(),
+            }],
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MetaData {
+    pub Key: String,
+    pub Value: String,
+    #[serde(rename = "@wcm:action", default)]
+    pub action: Action,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Credentials {
+    pub Domain: String,
+    pub Password: String,
+    pub Username: String,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InstallTo {
+    pub DiskID: u32,
+    pub PartitionID: u32,
+    #[serde(rename = "$preserved", default)]
+    pub preserved: Preserved,
+}
+
+/// (De)serialization for `xsd:boolean` values.
+///
+/// Values are always written as `true` or `false`, but any capitalization of those
+/// (as well as `1` and `0`) is accepted when reading.
+mod boolean {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::fmt;
+
+    struct Boolean(bool);
+
+    impl<'de> Deserialize<'de> for Boolean {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct BooleanVisitor;
+
+            impl de::Visitor<'_> for BooleanVisitor {
+                type Value = Boolean;
+
+                fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                    formatter.write_str("`true` or `false`")
+                }
+
+                fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+                    Ok(Boolean(value))
+                }
+
+                fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                    match value.trim() {
+                        "1" => Ok(Boolean(true)),
+                        "0" => Ok(Boolean(false)),
+                        value if value.eq_ignore_ascii_case("true") => Ok(Boolean(true)),
+                        value if value.eq_ignore_ascii_case("false") => Ok(Boolean(false)),
+                        _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+                    }
+                }
+            }
+
+            deserializer.deserialize_str(BooleanVisitor)
+        }
+    }
+
+    pub fn serialize<S: Serializer>(value: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(if *value { "true" } else { "false" })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+        Boolean::deserialize(deserializer).map(|value| value.0)
+    }
+
+    pub mod option {
+        use super::Boolean;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            value: &Option<bool>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<bool>, D::Error> {
+            Option::<Boolean>::deserialize(deserializer).map(|value| value.map(|value| value.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+    use std::str::FromStr;
+    use xml::reader::{EventReader, XmlEvent};
+    use xml::writer::EmitterConfig;
+
+    fn unformat(xml: &str) -> String {
+        let parser = EventReader::new(Cursor::new(xml.trim()));
+        let mut result = Vec::new();
+        let mut writer = EmitterConfig::new()
+            .perform_indent(false)
+            .write_document_declaration(false)
+            .create_writer(&mut result);
+
+        for event in parser {
+            match event {
+                Ok(XmlEvent::StartDocument { .. }) => {}
+                Ok(XmlEvent::Whitespace(_)) => {}
+                Ok(XmlEvent::Characters(ref chars)) if chars.trim().is_empty() => {}
+                Ok(event) => {
+                    if let Some(event) = event.as_writer_event() {
+                        writer.write(event).unwrap();
+                    }
+                }
+                Err(e) => panic!("Error parsing XML: {:?}", e),
+            }
+        }
+
+        String::from_utf8(result).expect("Invalid UTF-8 in XML string")
+    }
+
+    const UNATTEND_1: &str = r#"
             <?xml version="1.0" encoding="utf-8"?>
             <unattend xmlns="urn:schemas-microsoft-com:unattend">
               <settings pass="windowsPE">
@@ -432,12 +1853,7 @@ mod tests {
             </unattend>
         "#;
 
-        let _: UnattendXml = quick_xml::de::from_str(raw_xml).unwrap();
-    }
-
-    #[test]
-    fn test_deserialize_2() {
-        let raw_xml = r#"
+    const UNATTEND_2: &str = r#"
           <?xml version="1.0" encoding="utf-8"?>
           <unattend xmlns="urn:schemas-microsoft-com:unattend">
             <settings pass="windowsPE">
@@ -595,91 +2011,1546 @@ mod tests {
                 <DisableAutoDaylightTimeSet>false</DisableAutoDaylightTimeSet>
                 <FirstLogonCommands>
                   <SynchronousCommand wcm:action="add">
-                    <Description>Control Panel Classic View</Description>
-                    <Order>1</Order>
-                    <CommandLine>reg add "HKEY_CURRENT_USER\Software\Microsoft\Windows\CurrentVersion\Explorer\ControlPanel" /v StartupPage /t REG_DWORD /d 1 /f</CommandLine>
-                    <RequiresUserInput>true</RequiresUserInput>
+                    <Description>Control Panel Classic View</Description>
+                    <Order>1</Order>
+                    <CommandLine>reg add "HKEY_CURRENT_USER\Software\Microsoft\Windows\CurrentVersion\Explorer\ControlPanel" /v StartupPage /t REG_DWORD /d 1 /f</CommandLine>
+                    <RequiresUserInput>true</RequiresUserInput>
+                  </SynchronousCommand>
+                  <SynchronousCommand wcm:action="add">
+                    <Description>Control Panel Icon Size</Description>
+                    <Order>2</Order>
+                    <CommandLine>reg add "HKEY_CURRENT_USER\Software\Microsoft\Windows\CurrentVersion\Explorer\ControlPanel" /v AllItemsIconView /t REG_DWORD /d 1 /f</CommandLine>
+                    <RequiresUserInput>true</RequiresUserInput>
+                  </SynchronousCommand>
+                  <SynchronousCommand wcm:action="add">
+                    <Order>3</Order>
+                    <Description>Set-ExecutionPolicy Unrestricted</Description>
+                    <RequiresUserInput>false</RequiresUserInput>
+                    <CommandLine>cmd.exe /c powershell -Command "Set-ExecutionPolicy Unrestricted"</CommandLine>
+                  </SynchronousCommand>
+                  <SynchronousCommand wcm:action="add">
+                    <Order>4</Order>
+                    <Description>Fix Network</Description>
+                    <RequiresUserInput>false</RequiresUserInput>
+                    <CommandLine>cmd /q /c "FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\fixnetwork.ps1  cmd /c powershell -file %i:\fixnetwork.ps1"</CommandLine>
+                  </SynchronousCommand>
+
+                  <SynchronousCommand wcm:action="add">
+                    <Order>20</Order>
+                    <Description>Disable Updates</Description>
+                    <RequiresUserInput>false</RequiresUserInput>
+                    <CommandLine>cmd /q /c "FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\disableWindowsUpdates.ps1  cmd /c powershell -file %i:\disableWindowsUpdates.ps1"</CommandLine>
+                  </SynchronousCommand>
+
+                  <!-- Install Chocolatey -->
+                  <SynchronousCommand wcm:action="add">
+                    <Order>50</Order>
+                    <Description>Install Chocolatey</Description>
+                    <RequiresUserInput>false</RequiresUserInput>
+                    <CommandLine>cmd /q /c "FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\chocolatey.ps1  cmd /c powershell -file %i:\chocolatey.ps1"</CommandLine>
+                  </SynchronousCommand>
+
+                  <!-- Install Applications via Chocolatey -->
+                  <SynchronousCommand wcm:action="add">
+                    <Order>51</Order>
+                    <Description>Install Apps via Chocolatey</Description>
+                    <RequiresUserInput>false</RequiresUserInput>
+                    <CommandLine>cmd /q /c "FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\chocolatey-apps.cmd  cmd /c %i:\chocolatey-apps.cmd"</CommandLine>
+                  </SynchronousCommand>
+
+                  <!-- Disable Cortana Search bar -->
+                  <SynchronousCommand wcm:action="add">
+                      <CommandLine>reg.exe ADD HKCU\Software\Microsoft\Windows\CurrentVersion\Search /v SearchboxTaskbarMode /t REG_DWORD /d 0</CommandLine>
+                      <Order>97</Order>
+                      <Description>Disable Cortana</Description>
                   </SynchronousCommand>
+
+                  <!-- Enable UAC -->
                   <SynchronousCommand wcm:action="add">
-                    <Description>Control Panel Icon Size</Description>
-                    <Order>2</Order>
-                    <CommandLine>reg add "HKEY_CURRENT_USER\Software\Microsoft\Windows\CurrentVersion\Explorer\ControlPanel" /v AllItemsIconView /t REG_DWORD /d 1 /f</CommandLine>
-                    <RequiresUserInput>true</RequiresUserInput>
+                    <Order>98</Order>
+                    <Description>Enable UAC</Description>
+                    <RequiresUserInput>false</RequiresUserInput>
+                    <CommandLine>cmd /q /c "FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\enableUAC.ps1  cmd /c powershell -file %i:\enableUAC.ps1"</CommandLine>
                   </SynchronousCommand>
+
+                  <!-- Remove OneDrive Integration -->
                   <SynchronousCommand wcm:action="add">
-                    <Order>3</Order>
-                    <Description>Set-ExecutionPolicy Unrestricted</Description>
+                    <Order>499</Order>
+                    <Description>Enable UAC</Description>
                     <RequiresUserInput>false</RequiresUserInput>
-                    <CommandLine>cmd.exe /c powershell -Command "Set-ExecutionPolicy Unrestricted"</CommandLine>
+                    <CommandLine>cmd /q /c "FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\removeOneDrive.ps1  cmd /c powershell -file %i:\removeOneDrive.ps1"</CommandLine>
                   </SynchronousCommand>
+
+                  <!-- Restarts System so changes take effect -->
                   <SynchronousCommand wcm:action="add">
-                    <Order>4</Order>
-                    <Description>Fix Network</Description>
+                    <Order>500</Order>
+                    <Description>Restart Computer</Description>
                     <RequiresUserInput>false</RequiresUserInput>
-                    <CommandLine>cmd /q /c "FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\fixnetwork.ps1  cmd /c powershell -file %i:\fixnetwork.ps1"</CommandLine>
+                    <CommandLine>cmd.exe /c powershell.exe -command "restart-computer -force"</CommandLine>
                   </SynchronousCommand>
 
-                  <SynchronousCommand wcm:action="add">
-                    <Order>20</Order>
-                    <Description>Disable Updates</Description>
-                    <RequiresUserInput>false</RequiresUserInput>
-                    <CommandLine>cmd /q /c "FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\disableWindowsUpdates.ps1  cmd /c powershell -file %i:\disableWindowsUpdates.ps1"</CommandLine>
-                  </SynchronousCommand>
+                </FirstLogonCommands>
+                <TimeZone>Brisbane</TimeZone>
+              </component>
+            </settings>
+          </unattend>
+        "#;
+
+    #[test]
+    fn test_serialize_1() {
+        let raw_xml = r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <unattend xmlns="urn:schemas-microsoft-com:unattend">
+              <settings pass="windowsPE">
+                <component name="Microsoft-Windows-Setup" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+                </component>
+              </settings>
+            </unattend>
+        "#;
+
+        let unattended = UnattendXml {
+            xmlns: "urn:schemas-microsoft-com:unattend".into(),
+            servicing: None,
+            settings: vec![Settings {
+                component: vec![Component::Setup(Setup::default())],
+                pass: Pass::WindowsPE,
+                preserved: Preserved::default(),
+            }],
+            offlineImage: None,
+            preserved: Preserved::default(),
+            prolog: Vec::new(),
+        };
+        assert_eq!(
+            unformat(raw_xml),
+            unformat(&unattended.to_string().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_deserialize_1() {
+        UnattendXml::from_str(UNATTEND_1).unwrap();
+    }
+
+    #[test]
+    fn test_deserialize_2() {
+        UnattendXml::from_str(UNATTEND_2).unwrap();
+    }
+
+    #[test]
+    fn test_deserialize_unknown_pass() {
+        let raw_xml = r#"
+            <unattend xmlns="urn:schemas-microsoft-com:unattend">
+              <settings pass="oobesystem">
+              </settings>
+            </unattend>
+        "#;
+
+        let error = UnattendXml::from_str(raw_xml).err().unwrap().to_string();
+        assert!(error.contains("unknown variant `oobesystem`"), "{error}");
+        assert_eq!(Pass::OobeSystem.to_string(), "oobeSystem");
+        assert_eq!(Pass::WindowsPE.to_string(), "windowsPE");
+    }
+
+    #[test]
+    fn test_deserialize_misplaced_setting() {
+        let raw_xml = r#"
+            <unattend xmlns="urn:schemas-microsoft-com:unattend">
+              <settings pass="windowsPE">
+                <component name="Microsoft-Windows-Shell-Setup" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+                  <UserData>
+                    <AcceptEula>true</AcceptEula>
+                    <FullName>Full Name</FullName>
+                    <Organization>Organization</Organization>
+                    <ProductKey>
+                      <Key>12345-12345-12345-12345-12345</Key>
+                    </ProductKey>
+                  </UserData>
+                </component>
+              </settings>
+            </unattend>
+        "#;
+
+        // Settings of another component are kept as they are
+        let unattend = UnattendXml::from_str(raw_xml).unwrap();
+        let shell_setup = components(&unattend)
+            .find_map(|component| match component {
+                Component::ShellSetup(component) => Some(component),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(shell_setup.preserved.unknown.len(), 1);
+        assert_eq!(shell_setup.preserved.unknown[0].name, "UserData");
+
+        let xml = unattend.to_string_with(&WriteOptions::compact()).unwrap();
+        assert!(xml.contains(
+            "<UserData><AcceptEula>true</AcceptEula><FullName>Full Name</FullName><Organization>Organization</Organization><ProductKey><Key>12345-12345-12345-12345-12345</Key></ProductKey></UserData>"
+        ), "{xml}");
+    }
+
+    fn components(unattend: &UnattendXml) -> impl Iterator<Item = &Component> {
+        unattend
+            .settings
+            .iter()
+            .flat_map(|settings| &settings.component)
+    }
+
+    fn local_accounts(unattend: &UnattendXml) -> Vec<String> {
+        components(unattend)
+            .filter_map(|component| match component {
+                Component::ShellSetup(component) => component.UserAccounts.as_ref(),
+                _ => None,
+            })
+            .flat_map(|accounts| &accounts.LocalAccounts)
+            .flat_map(|accounts| &accounts.LocalAccount)
+            .map(|account| account.Name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip_local_accounts() {
+        for (raw_xml, expected) in [
+            (UNATTEND_1, vec!["admin", "user", "user2"]),
+            (UNATTEND_2, vec!["moe"]),
+        ] {
+            let unattend = UnattendXml::from_str(raw_xml).unwrap();
+            assert_eq!(local_accounts(&unattend), expected);
+
+            let unattend = UnattendXml::from_str(&unattend.to_string().unwrap()).unwrap();
+            assert_eq!(local_accounts(&unattend), expected);
+        }
+    }
+
+    #[test]
+    fn test_serialize_user_accounts() {
+        let raw_xml = r#"
+            <component name="Microsoft-Windows-Shell-Setup" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+              <UserAccounts>
+                <AdministratorPassword>
+                  <PlainText>true</PlainText>
+                  <Value>1234</Value>
+                </AdministratorPassword>
+                <DomainAccounts>
+                  <DomainAccountList wcm:action="add">
+                    <Domain>fabrikam</Domain>
+                    <DomainAccount wcm:action="add">
+                      <Group>Administrators</Group>
+                      <Name>user</Name>
+                    </DomainAccount>
+                  </DomainAccountList>
+                </DomainAccounts>
+              </UserAccounts>
+            </component>
+        "#;
+
+        let component = Component::ShellSetup(ShellSetup {
+            UserAccounts: Some(UserAccounts {
+                AdministratorPassword: Some(Password {
+                    PlainText: true,
+                    Value: "1234".into(),
+                    preserved: Preserved::default(),
+                }),
+                DomainAccounts: Some(DomainAccounts {
+                    DomainAccountList: vec![DomainAccountList {
+                        Domain: "fabrikam".into(),
+                        DomainAccount: vec![DomainAccount {
+                            Group: Some("Administrators".into()),
+                            Name: "user".into(),
+                            action: Action::Add,
+                            preserved: Preserved::default(),
+                        }],
+                        action: Action::Add,
+                        preserved: Preserved::default(),
+                    }],
+                    preserved: Preserved::default(),
+                }),
+                LocalAccounts: None,
+                preserved: Preserved::default(),
+            }),
+            ..Default::default()
+        });
+        assert_eq!(
+            unformat(raw_xml),
+            unformat(&se::to_string(&component).unwrap())
+        );
+    }
+
+    fn install_from(unattend: &UnattendXml) -> Option<&InstallFrom> {
+        components(unattend)
+            .find_map(|component| match component {
+                Component::Setup(component) => component.ImageInstall.as_ref(),
+                _ => None,
+            })
+            .and_then(|image| image.OSImage.InstallFrom.as_ref())
+    }
+
+    #[test]
+    fn test_round_trip_install_from() {
+        let unattend = UnattendXml::from_str(UNATTEND_1).unwrap();
+        let unattend = UnattendXml::from_str(&unattend.to_string().unwrap()).unwrap();
+
+        let install_from = install_from(&unattend).unwrap();
+        assert_eq!(install_from.MetaData.len(), 1);
+        assert_eq!(install_from.MetaData[0].Key, "/IMAGE/DESCRIPTION");
+        assert_eq!(install_from.MetaData[0].Value, "Windows 10 Home");
+        assert_eq!(install_from.MetaData[0].action, Action::Add);
+    }
+
+    #[test]
+    fn test_serialize_install_from() {
+        let raw_xml = r#"
+            <component name="Microsoft-Windows-Setup" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+              <ImageInstall>
+                <OSImage>
+                  <InstallFrom>
+                    <MetaData wcm:action="add">
+                      <Key>/IMAGE/INDEX</Key>
+                      <Value>6</Value>
+                    </MetaData>
+                  </InstallFrom>
+                </OSImage>
+              </ImageInstall>
+            </component>
+        "#;
+
+        let component = Component::Setup(Setup {
+            ImageInstall: Some(ImageInstall {
+                OSImage: OSImage {
+                    InstallFrom: Some(InstallFrom::by_index(6)),
+                    InstallTo: None,
+                    InstallToAvailablePartition: None,
+                    WillShowUI: None,
+                    preserved: Preserved::default(),
+                },
+                preserved: Preserved::default(),
+            }),
+            ..Default::default()
+        });
+        assert_eq!(
+            unformat(raw_xml),
+            unformat(&se::to_string(&component).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_round_trip_auto_logon() {
+        let unattend = UnattendXml::from_str(UNATTEND_2).unwrap();
+        let unattend = UnattendXml::from_str(&unattend.to_string().unwrap()).unwrap();
+
+        let auto_logon = components(&unattend)
+            .find_map(|component| match component {
+                Component::ShellSetup(component) => component.AutoLogon.as_ref(),
+                _ => None,
+            })
+            .unwrap();
+        assert!(auto_logon.Enabled);
+        assert_eq!(auto_logon.Username, "moe");
+        assert!(auto_logon.Password.as_ref().unwrap().PlainText);
+        assert!(auto_logon.Domain.is_none());
+        assert!(auto_logon.LogonCount.is_none());
+    }
+
+    fn find_oobe(unattend: &UnattendXml) -> Option<&OOBE> {
+        components(unattend).find_map(|component| match component {
+            Component::ShellSetup(component) => component.OOBE.as_ref(),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_round_trip_oobe() {
+        let unattend = UnattendXml::from_str(UNATTEND_1).unwrap();
+        let oobe = find_oobe(&unattend).unwrap();
+        assert_eq!(oobe.ProtectYourPC, Some(ProtectYourPC::Disabled));
+        assert_eq!(oobe.HideLocalAccountScreen, Some(true));
+        assert_eq!(oobe.NetworkLocation, None);
+
+        let unattend = UnattendXml::from_str(UNATTEND_2).unwrap();
+        let unattend = UnattendXml::from_str(&unattend.to_string().unwrap()).unwrap();
+        let oobe = find_oobe(&unattend).unwrap();
+        assert_eq!(oobe.ProtectYourPC, Some(ProtectYourPC::Recommended));
+        assert_eq!(oobe.NetworkLocation, Some(NetworkLocation::Home));
+        assert_eq!(oobe.SkipMachineOOBE, Some(true));
+        assert_eq!(oobe.HideLocalAccountScreen, None);
+    }
+
+    #[test]
+    fn test_round_trip_driver_paths() {
+        let unattend = UnattendXml::from_str(UNATTEND_1).unwrap();
+        let unattend = UnattendXml::from_str(&unattend.to_string().unwrap()).unwrap();
+
+        let driver_paths = components(&unattend)
+            .find_map(|component| match component {
+                Component::PnpCustomizationsWinPE(component) => component.DriverPaths.as_ref(),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            driver_paths
+                .PathAndCredentials
+                .iter()
+                .map(|path| (path.keyValue.as_str(), path.Path.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("1", "C:\\Drivers"),
+                ("2", "D:\\Drivers"),
+                ("3", "E:\\Drivers"),
+                ("4", "X:\\Drivers"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_driver_paths() {
+        let raw_xml = r#"
+            <component name="Microsoft-Windows-PnpCustomizationsNonWinPE" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+              <DriverPaths>
+                <PathAndCredentials wcm:keyValue="1" wcm:action="add">
+                  <Credentials>
+                    <Domain>fabrikam</Domain>
+                    <Password>1234</Password>
+                    <Username>user</Username>
+                  </Credentials>
+                  <Path>\\server\drivers</Path>
+                </PathAndCredentials>
+              </DriverPaths>
+            </component>
+        "#;
+
+        let component = Component::PnpCustomizationsNonWinPE(PnpCustomizationsNonWinPE {
+            DriverPaths: Some(DriverPaths {
+                PathAndCredentials: vec![PathAndCredentials {
+                    Credentials: Some(Credentials {
+                        Domain: "fabrikam".into(),
+                        Password: "1234".into(),
+                        Username: "user".into(),
+                        preserved: Preserved::default(),
+                    }),
+                    Path: "\\\\server\\drivers".into(),
+                    keyValue: "1".into(),
+                    action: Action::Add,
+                    preserved: Preserved::default(),
+                }],
+                preserved: Preserved::default(),
+            }),
+            ..Default::default()
+        });
+        assert_eq!(
+            unformat(raw_xml),
+            unformat(&se::to_string(&component).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_deserialize_boolean() {
+        let user_data = |accept_eula: &str| {
+            de::from_str::<UserData>(&format!(
+                "<UserData>
+                  <AcceptEula>{accept_eula}</AcceptEula>
+                  <FullName>Full Name</FullName>
+                  <Organization>Organization</Organization>
+                  <ProductKey>
+                    <Key>12345-12345-12345-12345-12345</Key>
+                  </ProductKey>
+                </UserData>"
+            ))
+        };
+
+        for (value, expected) in [
+            ("true", true),
+            ("True", true),
+            ("FALSE", false),
+            ("0", false),
+        ] {
+            assert_eq!(user_data(value).unwrap().AcceptEula, expected);
+        }
+        assert!(user_data("yes").is_err());
+
+        let serialized = se::to_string(&user_data("True").unwrap()).unwrap();
+        assert!(
+            serialized.contains("<AcceptEula>true</AcceptEula>"),
+            "{serialized}"
+        );
+    }
+
+    #[test]
+    fn test_deserialize_numeric_order() {
+        let command = |order: &str| {
+            de::from_str::<SynchronousCommand>(&format!(
+                r#"<SynchronousCommand wcm:action="add">
+                  <CommandLine>cmd.exe</CommandLine>
+                  <Order>{order}</Order>
+                </SynchronousCommand>"#
+            ))
+        };
+
+        assert_eq!(command("20").unwrap().Order, 20);
+        assert!(command("twenty").is_err());
+        assert!(command("-1").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_action() {
+        let command = |action: &str| {
+            de::from_str::<SynchronousCommand>(&format!(
+                r#"<SynchronousCommand {action}>
+                  <CommandLine>cmd.exe</CommandLine>
+                  <Order>1</Order>
+                </SynchronousCommand>"#
+            ))
+        };
+
+        assert_eq!(command("").unwrap().action, Action::Add);
+        assert_eq!(command(r#"wcm:action="add""#).unwrap().action, Action::Add);
+        assert_eq!(
+            command(r#"wcm:action="modify""#).unwrap().action,
+            Action::Modify
+        );
+        assert_eq!(
+            command(r#"wcm:action="remove""#).unwrap().action,
+            Action::Remove
+        );
+        assert!(command(r#"wcm:action="delete""#).is_err());
+
+        let serialized = se::to_string(&command("").unwrap()).unwrap();
+        assert!(serialized.contains(r#"wcm:action="add""#), "{serialized}");
+    }
+
+    /// A directory of its own for a test, removed when the test ends.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("serde_win_unattend_{name}_{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_write_to_missing_directory() {
+        let unattend = UnattendXml::from_str(UNATTEND_1).unwrap();
+        let root = TempDir::new("missing_directory");
+
+        let path = root.0.join("missing");
+        assert!(matches!(unattend.write_to(&path), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn test_write_to_wrong_namespace() {
+        let mut unattend = UnattendXml::from_str(UNATTEND_1).unwrap();
+        unattend.xmlns = "urn:schemas-microsoft-com:unattended".into();
+        let root = TempDir::new("wrong_namespace");
+
+        let error = unattend.write_to(&root.0).err().unwrap();
+        assert!(matches!(error, Error::Validation(_)));
+        assert_eq!(
+            error.to_string(),
+            "invalid answer file: the namespace must be `urn:schemas-microsoft-com:unattend`, not `urn:schemas-microsoft-com:unattended`"
+        );
+        assert!(!root.0.join("Autounattend.xml").exists());
+    }
+
+    #[test]
+    fn test_round_trip_path() {
+        let root = TempDir::new("round_trip_path");
+        let path = root.0.join("unattend.xml");
+        UnattendXml::from_reader(UNATTEND_2.as_bytes())
+            .unwrap()
+            .to_writer(std::fs::File::create(&path).unwrap())
+            .unwrap();
+
+        let unattend = UnattendXml::from_path(&path).unwrap();
+        assert_eq!(local_accounts(&unattend), vec!["moe"]);
+    }
+
+    #[test]
+    fn test_write_to_location() {
+        let root = TempDir::new("write_to_location");
+        let root = &root.0;
+        let unattend = UnattendXml::from_str(UNATTEND_2).unwrap();
+
+        let path = unattend
+            .write_to_location(root, SearchLocation::OemPanther)
+            .unwrap();
+        assert_eq!(
+            path,
+            root.join("sources")
+                .join("$OEM$")
+                .join("$$")
+                .join("Panther")
+                .join("unattend.xml")
+        );
+        assert_eq!(
+            local_accounts(&UnattendXml::from_path(&path).unwrap()),
+            vec!["moe"]
+        );
+        let options = WriteOptions::compact();
+        let path = unattend
+            .write_to_location_with(root, SearchLocation::Sources, &options)
+            .unwrap();
+        assert_eq!(path, root.join("sources").join("Autounattend.xml"));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            unattend.to_string_with(&options).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_round_trip_text() {
+        let raw_xml = "<unattend xmlns=\"urn:schemas-microsoft-com:unattend\"><settings pass=\"specialize\"><component name=\"Microsoft-Windows-Deployment\" processorArchitecture=\"amd64\" publicKeyToken=\"31bf3856ad364e35\" language=\"neutral\" versionScope=\"nonSxS\" xmlns:wcm=\"http://schemas.microsoft.com/WMIConfig/2002/State\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\"><RunSynchronous><RunSynchronousCommand wcm:action=\"add\"><Path> cmd /c echo  two  spaces </Path><Description>\n  Indented\n</Description><Order>1</Order></RunSynchronousCommand></RunSynchronous></component></settings></unattend>";
+        let unattend = UnattendXml::from_str(raw_xml).unwrap();
+        let command = components(&unattend)
+            .find_map(|component| match component {
+                Component::Deployment(component) => component.RunSynchronous.as_ref(),
+                _ => None,
+            })
+            .map(|run| &run.RunSynchronousCommand[0])
+            .unwrap();
+        assert_eq!(command.Path, " cmd /c echo  two  spaces ");
+        assert_eq!(command.Description.as_deref(), Some("\n  Indented\n"));
+        assert_eq!(
+            unattend.to_string_with(&WriteOptions::compact()).unwrap(),
+            raw_xml
+        );
+    }
+
+    #[test]
+    fn test_round_trip_unknown_settings() {
+        let unattend = UnattendXml::from_str(UNATTEND_2).unwrap();
+        let xml = unattend.to_string().unwrap();
+        for setting in [
+            "<EnableFirewall>true</EnableFirewall>",
+            "<EnableLUA>false</EnableLUA>",
+            "<SkipRearm>1</SkipRearm>",
+            "<SkipAutoActivation>true</SkipAutoActivation>",
+            "<CEIPEnabled>0</CEIPEnabled>",
+            "<RegisteredOrganization>Comprofix</RegisteredOrganization>",
+            "<RegisteredOwner>moe</RegisteredOwner>",
+            "<DisableAutoDaylightTimeSet>false</DisableAutoDaylightTimeSet>",
+            "<TimeZone>Brisbane</TimeZone>",
+            "<TypeID>0x27</TypeID>",
+        ] {
+            assert!(xml.contains(setting), "{setting} missing from {xml}");
+        }
+
+        // Unknown content is stable across further round trips
+        assert_eq!(
+            UnattendXml::from_str(&xml).unwrap().to_string().unwrap(),
+            xml
+        );
+
+        // Components that aren't modelled keep settings that share a name with a
+        // modelled one
+        let raw_xml = r#"<unattend xmlns="urn:schemas-microsoft-com:unattend"><settings pass="specialize"><component name="Microsoft-Windows-Foo" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"><Foo>1</Foo><ComputerName>pc</ComputerName></component></settings></unattend>"#;
+        let unattend = UnattendXml::from_str(raw_xml).unwrap();
+        match &unattend.settings[0].component[0] {
+            Component::Other(component) => assert_eq!(component.preserved.unknown.len(), 2),
+            _ => panic!("expected an unmodelled component"),
+        }
+        assert_eq!(
+            unattend.to_string_with(&WriteOptions::compact()).unwrap(),
+            raw_xml
+        );
+        // Settings without any components read back too
+        let raw_xml = r#"<unattend xmlns="urn:schemas-microsoft-com:unattend"><settings pass="oobeSystem"/></unattend>"#;
+        let unattend = UnattendXml::from_str(raw_xml).unwrap();
+        assert!(unattend.settings[0].component.is_empty());
+        assert_eq!(
+            unattend.to_string_with(&WriteOptions::compact()).unwrap(),
+            raw_xml
+        );
+    }
+
+    #[test]
+    fn test_round_trip_comments() {
+        let unattend = UnattendXml::from_str(UNATTEND_2).unwrap();
+        let commands = components(&unattend)
+            .find_map(|component| match component {
+                Component::ShellSetup(component) => component.FirstLogonCommands.as_ref(),
+                _ => None,
+            })
+            .unwrap();
+        assert!(commands.preserved.comments.contains(&Comment {
+            text: " Install Chocolatey ".into(),
+            before: Some("SynchronousCommand".into()),
+            index: 5,
+        }));
+
+        let xml = unattend.to_string_with(&WriteOptions::compact()).unwrap();
+        for comment in [
+            "<!-- Install Chocolatey --><SynchronousCommand",
+            "<!-- Restarts System so changes take effect --><SynchronousCommand",
+            "--><ProductKey>",
+        ] {
+            assert!(xml.contains(comment), "{comment} missing from {xml}");
+        }
+        assert_eq!(
+            UnattendXml::from_str(&xml)
+                .unwrap()
+                .to_string_with(&WriteOptions::compact())
+                .unwrap(),
+            xml
+        );
+
+        let xml = UnattendXml::from_str(UNATTEND_1)
+            .unwrap()
+            .to_string_with(&WriteOptions::compact())
+            .unwrap();
+        assert!(xml.contains(
+            "<!-- Windows 10 English: en-US --><!-- Windows 10 English International: en-GB --><UILanguage>"
+        ));
+
+        // Comments before the root element are kept, while comments within the text
+        // of a setting are dropped
+        let raw_xml = r#"<?xml version="1.0" encoding="utf-8"?>
+            <!-- Generated by hand -->
+            <unattend xmlns="urn:schemas-microsoft-com:unattend">
+              <settings pass="specialize">
+                <component name="Microsoft-Windows-Shell-Setup" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+                  <ComputerName><!-- n -->pc</ComputerName>
+                </component>
+              </settings>
+            </unattend>
+            <!-- End of file -->
+        "#;
+        let unattend = UnattendXml::from_str(raw_xml).unwrap();
+        assert_eq!(unattend.prolog, vec![" Generated by hand ".to_string()]);
+        let xml = unattend.to_string().unwrap();
+        assert!(
+            xml.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\r\n<!-- Generated by hand -->\r\n<unattend "),
+            "{xml}"
+        );
+        assert!(xml.contains("<ComputerName>pc</ComputerName>"), "{xml}");
+        assert!(!xml.contains("End of file"), "{xml}");
+        assert_eq!(
+            UnattendXml::from_str(&xml).unwrap().to_string().unwrap(),
+            xml
+        );
+
+        // Comments stay in place among unknown elements too
+        let raw_xml = r#"<unattend xmlns="urn:schemas-microsoft-com:unattend"><settings pass="specialize"><component name="Microsoft-Windows-Foo" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"><Foo>1</Foo><!-- name --><ComputerName>pc</ComputerName><!-- end --></component></settings></unattend>"#;
+        assert_eq!(
+            UnattendXml::from_str(raw_xml)
+                .unwrap()
+                .to_string_with(&WriteOptions::compact())
+                .unwrap(),
+            raw_xml
+        );
+    }
 
-                  <!-- Install Chocolatey -->
-                  <SynchronousCommand wcm:action="add">
-                    <Order>50</Order>
-                    <Description>Install Chocolatey</Description>
-                    <RequiresUserInput>false</RequiresUserInput>
-                    <CommandLine>cmd /q /c "FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\chocolatey.ps1  cmd /c powershell -file %i:\chocolatey.ps1"</CommandLine>
-                  </SynchronousCommand>
+    #[test]
+    fn test_serialize_comments() {
+        let commands = FirstLogonCommands {
+            SynchronousCommand: vec![SynchronousCommand {
+                CommandLine: "cmd /c exit".into(),
+                Description: None,
+                Order: 1,
+                RequiresUserInput: None,
+                action: Action::Add,
+                preserved: Preserved::default(),
+            }],
+            preserved: Preserved {
+                unknown: Vec::new(),
+                comments: vec![
+                    Comment::before("First command", "SynchronousCommand"),
+                    Comment::trailing("End of commands"),
+                ],
+            },
+        };
+        assert_eq!(
+            se::to_string(&commands).unwrap(),
+            "<FirstLogonCommands><!--First command--><SynchronousCommand wcm:action=\"add\"><CommandLine>cmd /c exit</CommandLine><Order>1</Order></SynchronousCommand><!--End of commands--></FirstLogonCommands>"
+        );
+    }
 
-                  <!-- Install Applications via Chocolatey -->
-                  <SynchronousCommand wcm:action="add">
-                    <Order>51</Order>
-                    <Description>Install Apps via Chocolatey</Description>
-                    <RequiresUserInput>false</RequiresUserInput>
-                    <CommandLine>cmd /q /c "FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\chocolatey-apps.cmd  cmd /c %i:\chocolatey-apps.cmd"</CommandLine>
-                  </SynchronousCommand>
+    #[test]
+    fn test_write_options() {
+        let unattend = UnattendXml {
+            xmlns: "urn:schemas-microsoft-com:unattend".into(),
+            servicing: None,
+            settings: vec![Settings {
+                component: Vec::new(),
+                pass: Pass::OobeSystem,
+                preserved: Preserved {
+                    unknown: Vec::new(),
+                    comments: vec![Comment::trailing(" empty ")],
+                },
+            }],
+            offlineImage: None,
+            preserved: Preserved::default(),
+            prolog: Vec::new(),
+        };
 
-                  <!-- Disable Cortana Search bar -->
-                  <SynchronousCommand wcm:action="add">
-                      <CommandLine>reg.exe ADD HKCU\Software\Microsoft\Windows\CurrentVersion\Search /v SearchboxTaskbarMode /t REG_DWORD /d 0</CommandLine>
-                      <Order>97</Order>
-                      <Description>Disable Cortana</Description>
-                  </SynchronousCommand>
+        assert_eq!(
+            unattend.to_string().unwrap(),
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\r\n\
+             <unattend xmlns=\"urn:schemas-microsoft-com:unattend\">\r\n\
+             \x20   <settings pass=\"oobeSystem\">\r\n\
+             \x20       <!-- empty -->\r\n\
+             \x20   </settings>\r\n\
+             </unattend>"
+        );
+        assert_eq!(
+            unattend.to_string_with(&WriteOptions::compact()).unwrap(),
+            "<unattend xmlns=\"urn:schemas-microsoft-com:unattend\"><settings pass=\"oobeSystem\"><!-- empty --></settings></unattend>"
+        );
+        assert_eq!(
+            unattend
+                .to_string_with(&WriteOptions {
+                    declaration: false,
+                    indent: Some(2),
+                    line_ending: LineEnding::Lf,
+                    bom: true,
+                    encoding: Encoding::Utf8,
+                    namespaces: NamespaceDeclarations::PerComponent,
+                    validate: false,
+                })
+                .unwrap(),
+            "\u{feff}<unattend xmlns=\"urn:schemas-microsoft-com:unattend\">\n  <settings pass=\"oobeSystem\">\n    <!-- empty -->\n  </settings>\n</unattend>"
+        );
 
-                  <!-- Enable UAC -->
-                  <SynchronousCommand wcm:action="add">
-                    <Order>98</Order>
-                    <Description>Enable UAC</Description>
-                    <RequiresUserInput>false</RequiresUserInput>
-                    <CommandLine>cmd /q /c "FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\enableUAC.ps1  cmd /c powershell -file %i:\enableUAC.ps1"</CommandLine>
-                  </SynchronousCommand>
+        let compact = unattend.to_string_with(&WriteOptions::compact()).unwrap();
+        assert_eq!(
+            UnattendXml::from_str(&compact)
+                .unwrap()
+                .to_string_with(&WriteOptions::compact())
+                .unwrap(),
+            compact
+        );
 
-                  <!-- Remove OneDrive Integration -->
-                  <SynchronousCommand wcm:action="add">
-                    <Order>499</Order>
-                    <Description>Enable UAC</Description>
-                    <RequiresUserInput>false</RequiresUserInput>
-                    <CommandLine>cmd /q /c "FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\removeOneDrive.ps1  cmd /c powershell -file %i:\removeOneDrive.ps1"</CommandLine>
-                  </SynchronousCommand>
+        // Only the line breaks between elements follow the line ending, while those
+        // within text and comments are kept
+        let raw_xml = "<unattend xmlns=\"urn:schemas-microsoft-com:unattend\"><settings pass=\"specialize\"><component name=\"Microsoft-Windows-Shell-Setup\" processorArchitecture=\"amd64\" publicKeyToken=\"31bf3856ad364e35\" language=\"neutral\" versionScope=\"nonSxS\" xmlns:wcm=\"http://schemas.microsoft.com/WMIConfig/2002/State\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\"><!-- first\nsecond --><ComputerName>\npc\r\n</ComputerName></component></settings></unattend>";
+        let xml = UnattendXml::from_str(raw_xml).unwrap().to_string().unwrap();
+        assert!(
+            xml.contains("\r\n            <!-- first\nsecond -->\r\n            <ComputerName>\npc\r\n</ComputerName>\r\n        </component>"),
+            "{xml:?}"
+        );
+        assert_eq!(
+            UnattendXml::from_str(&xml)
+                .unwrap()
+                .to_string_with(&WriteOptions::compact())
+                .unwrap(),
+            raw_xml
+        );
+    }
 
-                  <!-- Restarts System so changes take effect -->
-                  <SynchronousCommand wcm:action="add">
-                    <Order>500</Order>
-                    <Description>Restart Computer</Description>
-                    <RequiresUserInput>false</RequiresUserInput>
-                    <CommandLine>cmd.exe /c powershell.exe -command "restart-computer -force"</CommandLine>
-                  </SynchronousCommand>
+    #[test]
+    fn test_read_encodings() {
+        let xml = UNATTEND_2.trim().replace("utf-8", "utf-16");
+        let utf16le: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain(xml.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let utf16be: Vec<u8> = xml.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let utf8 = [&[0xEF, 0xBB, 0xBF], UNATTEND_2.trim().as_bytes()].concat();
 
-                </FirstLogonCommands>
-                <TimeZone>Brisbane</TimeZone>
-              </component>
-            </settings>
-          </unattend>
+        let expected = UnattendXml::from_str(UNATTEND_2)
+            .unwrap()
+            .to_string()
+            .unwrap();
+        for bytes in [utf16le, utf16be, utf8] {
+            let unattend = UnattendXml::from_reader(Cursor::new(bytes)).unwrap();
+            assert_eq!(unattend.to_string().unwrap(), expected);
+        }
+
+        let error = UnattendXml::from_slice(
+            b"<?xml version=\"1.0\" encoding=\"shift_jis\"?><unattend xmlns=\"\"/>",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert!(
+            error.contains("unsupported encoding `shift_jis`"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_write_utf16() {
+        let unattend = UnattendXml::from_str(UNATTEND_1).unwrap();
+        let options = WriteOptions {
+            encoding: Encoding::Utf16,
+            ..Default::default()
+        };
+        let bytes = unattend.to_vec_with(&options).unwrap();
+        assert_eq!(&bytes[..4], &[0xFF, 0xFE, b'<', 0]);
+        assert!(String::from_utf16_lossy(
+            &bytes[2..]
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect::<Vec<_>>()
+        )
+        .starts_with("<?xml version=\"1.0\" encoding=\"utf-16\"?>"));
+
+        assert_eq!(
+            UnattendXml::from_slice(&bytes)
+                .unwrap()
+                .to_string()
+                .unwrap(),
+            unattend.to_string().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_round_trip_offline_image() {
+        let unattend = UnattendXml::from_str(UNATTEND_1).unwrap();
+        let image = unattend.offlineImage.as_ref().unwrap();
+        assert_eq!(
+            image.source,
+            "catalog://doo/lina/sources/install_windows 10 home.clg"
+        );
+        assert!(unattend.preserved.unknown.is_empty());
+
+        let mut unattend = UnattendXml::from_str(&unattend.to_string().unwrap()).unwrap();
+        assert_eq!(
+            unattend.offlineImage.as_ref().unwrap().source,
+            "catalog://doo/lina/sources/install_windows 10 home.clg"
+        );
+
+        unattend.offlineImage = Some(OfflineImage::new("catalog://server/share/install.clg"));
+        assert!(unattend
+            .to_string()
+            .unwrap()
+            .contains("<cpi:offlineImage cpi:source=\"catalog://server/share/install.clg\" xmlns:cpi=\"urn:schemas-microsoft-com:cpi\"/>"));
+    }
+
+    #[test]
+    fn test_namespace_prefixes() {
+        let raw_xml = r#"
+            <u:unattend xmlns:u="urn:schemas-microsoft-com:unattend" xmlns:state="http://schemas.microsoft.com/WMIConfig/2002/State">
+              <u:settings pass="oobeSystem">
+                <u:component name="Microsoft-Windows-Shell-Setup" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS">
+                  <u:UserAccounts>
+                    <u:LocalAccounts>
+                      <u:LocalAccount state:action="modify">
+                        <u:Name>moe</u:Name>
+                        <u:Password>
+                          <u:PlainText>true</u:PlainText>
+                          <u:Value></u:Value>
+                        </u:Password>
+                      </u:LocalAccount>
+                    </u:LocalAccounts>
+                  </u:UserAccounts>
+                </u:component>
+              </u:settings>
+            </u:unattend>
+        "#;
+        let action = |unattend: &UnattendXml| {
+            unattend
+                .settings
+                .iter()
+                .flat_map(|settings| &settings.component)
+                .find_map(|component| match component {
+                    Component::ShellSetup(component) => component.UserAccounts.as_ref(),
+                    _ => None,
+                })
+                .and_then(|accounts| accounts.LocalAccounts.as_ref())
+                .map(|accounts| accounts.LocalAccount[0].action)
+        };
+        let unattend = UnattendXml::from_str(raw_xml).unwrap();
+        assert_eq!(action(&unattend), Some(Action::Modify));
+
+        let xml = unattend.to_string_with(&WriteOptions::compact()).unwrap();
+        assert!(xml.starts_with("<unattend xmlns=\"urn:schemas-microsoft-com:unattend\"><settings"));
+        assert!(xml.contains("versionScope=\"nonSxS\" xmlns:wcm=\"http://schemas.microsoft.com/WMIConfig/2002/State\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">"));
+        assert!(xml.contains("<LocalAccount wcm:action=\"modify\">"));
+
+        let xml = unattend
+            .to_string_with(&WriteOptions {
+                namespaces: NamespaceDeclarations::Root,
+                ..WriteOptions::compact()
+            })
+            .unwrap();
+        assert!(xml.starts_with("<unattend xmlns=\"urn:schemas-microsoft-com:unattend\" xmlns:wcm=\"http://schemas.microsoft.com/WMIConfig/2002/State\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\"><settings"));
+        assert!(xml.contains("versionScope=\"nonSxS\"><UserAccounts>"));
+        assert_eq!(
+            action(&UnattendXml::from_str(&xml).unwrap()),
+            Some(Action::Modify)
+        );
+
+        // Declarations of other namespaces go with the unknown elements that use them
+        let raw_xml = r#"
+            <unattend xmlns="urn:schemas-microsoft-com:unattend" xmlns:foo="urn:foo">
+              <settings pass="oobeSystem">
+                <component name="Microsoft-Windows-Shell-Setup" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:bar="urn:bar">
+                  <foo:Foo>1</foo:Foo>
+                  <bar:Bar bar:id="2"><bar:Baz>3</bar:Baz></bar:Bar>
+                </component>
+              </settings>
+            </unattend>
+        "#;
+        let xml = UnattendXml::from_str(raw_xml)
+            .unwrap()
+            .to_string_with(&WriteOptions::compact())
+            .unwrap();
+        assert!(
+            xml.contains(r#"<foo:Foo xmlns:foo="urn:foo">1</foo:Foo>"#),
+            "{xml}"
+        );
+        assert!(
+            xml.contains(
+                r#"<bar:Bar bar:id="2" xmlns:bar="urn:bar"><bar:Baz>3</bar:Baz></bar:Bar>"#
+            ),
+            "{xml}"
+        );
+        // Every prefix is bound, which the parser behind `unformat` insists on
+        unformat(&xml);
+    }
+
+    #[test]
+    fn test_round_trip_servicing() {
+        let raw_xml = r#"
+            <unattend xmlns="urn:schemas-microsoft-com:unattend">
+              <servicing>
+                <package action="configure">
+                  <assemblyIdentity name="Microsoft-Windows-Foundation-Package" version="10.0.19041.1" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="" />
+                  <selection name="TelnetClient" state="true" />
+                  <selection name="SMB1Protocol" state="false" />
+                </package>
+                <package action="install">
+                  <assemblyIdentity name="Microsoft-Windows-Client-LanguagePack-Package" version="10.0.19041.1" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="de-DE" />
+                  <source location="\\server\share\lp.cab" />
+                </package>
+              </servicing>
+            </unattend>
+        "#;
+        let unattend = UnattendXml::from_str(raw_xml).unwrap();
+        assert!(unattend.settings.is_empty());
+        let packages = &unattend.servicing.as_ref().unwrap().package;
+        assert_eq!(packages[0].action, PackageAction::Configure);
+        assert_eq!(packages[0].assemblyIdentity.language, "");
+        assert_eq!(packages[0].selection[1].name, "SMB1Protocol");
+        assert!(!packages[0].selection[1].state);
+        assert_eq!(packages[1].action, PackageAction::Install);
+        assert_eq!(
+            packages[1].source.as_ref().unwrap().location,
+            "\\\\server\\share\\lp.cab"
+        );
+
+        assert_eq!(unformat(raw_xml), unformat(&unattend.to_string().unwrap()));
+    }
+
+    #[test]
+    fn test_serialize_servicing() {
+        let servicing = Servicing {
+            package: vec![
+                Package::configure(
+                    AssemblyIdentity::new("Microsoft-Windows-Foundation-Package", "10.0.19041.1"),
+                    vec![Selection::new("TelnetClient", true)],
+                ),
+                Package::remove(AssemblyIdentity::new("Package_for_KB000000", "10.0.1.0")),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            se::to_string(&servicing).unwrap(),
+            concat!(
+                "<servicing>",
+                "<package action=\"configure\">",
+                "<assemblyIdentity name=\"Microsoft-Windows-Foundation-Package\" version=\"10.0.19041.1\" processorArchitecture=\"amd64\" publicKeyToken=\"31bf3856ad364e35\" language=\"neutral\"/>",
+                "<selection name=\"TelnetClient\" state=\"true\"/>",
+                "</package>",
+                "<package action=\"remove\">",
+                "<assemblyIdentity name=\"Package_for_KB000000\" version=\"10.0.1.0\" processorArchitecture=\"amd64\" publicKeyToken=\"31bf3856ad364e35\" language=\"neutral\"/>",
+                "</package>",
+                "</servicing>",
+            )
+        );
+
+        let empty = UnattendXml {
+            servicing: Some(Servicing::default()),
+            ..UnattendXml::from_str(UNATTEND_2).unwrap()
+        };
+        let written = empty.to_string().unwrap();
+        assert!(written.contains("<servicing/>"), "{written}");
+        let read = UnattendXml::from_str(&written).unwrap();
+        assert!(read.servicing.unwrap().package.is_empty());
+    }
+
+    #[test]
+    fn test_validate_passes() {
+        for fixture in [UNATTEND_1, UNATTEND_2] {
+            let diagnostics = UnattendXml::from_str(fixture).unwrap().validate();
+            assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        }
+
+        let raw_xml = r#"
+            <unattend xmlns="urn:schemas-microsoft-com:unattend">
+              <settings pass="windowsPE">
+                <component name="Microsoft-Windows-Shell-Setup" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+                  <ComputerName>pc</ComputerName>
+                </component>
+              </settings>
+              <settings pass="oobeSystem">
+                <component name="Microsoft-Windows-Setup" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+                </component>
+                <component name="Microsoft-Windows-Shell-Setup" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+                  <ComputerName>pc</ComputerName>
+                </component>
+                <component name="Microsoft-Windows-Contoso" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+                </component>
+              </settings>
+            </unattend>
+        "#;
+        let diagnostics = UnattendXml::from_str(raw_xml).unwrap().validate();
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    pass: Pass::WindowsPE,
+                    component: ShellSetup::NAME.into(),
+                    setting: None,
+                    message: "component is not valid in the windowsPE pass".into(),
+                },
+                Diagnostic {
+                    pass: Pass::OobeSystem,
+                    component: Setup::NAME.into(),
+                    setting: None,
+                    message: "component is not valid in the oobeSystem pass".into(),
+                },
+                Diagnostic {
+                    pass: Pass::OobeSystem,
+                    component: ShellSetup::NAME.into(),
+                    setting: Some("ComputerName".into()),
+                    message: "setting is not valid in the oobeSystem pass".into(),
+                },
+            ]
+        );
+        assert_eq!(
+            diagnostics[2].to_string(),
+            "oobeSystem / Microsoft-Windows-Shell-Setup / ComputerName: setting is not valid in the oobeSystem pass"
+        );
+
+        let options = WriteOptions {
+            validate: true,
+            ..Default::default()
+        };
+        let unattend = UnattendXml::from_str(raw_xml).unwrap();
+        let error = unattend.to_string_with(&options).err().unwrap();
+        assert!(matches!(error, Error::Validation(_)));
+        assert!(
+            error.to_string().contains(&diagnostics[2].to_string()),
+            "{error}"
+        );
+        assert!(unattend.to_string().is_ok());
+        assert!(UnattendXml::from_str(UNATTEND_2)
+            .unwrap()
+            .to_string_with(&options)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_validate_misplaced_settings() {
+        let raw_xml = r#"
+            <unattend xmlns="urn:schemas-microsoft-com:unattend">
+              <settings pass="windowsPE">
+                <component name="Microsoft-Windows-Setup" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+                  <ComputerName>pc</ComputerName>
+                  <EnableNetwork>true</EnableNetwork>
+                </component>
+              </settings>
+            </unattend>
+        "#;
+        let diagnostics = UnattendXml::from_str(raw_xml).unwrap().validate();
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                pass: Pass::WindowsPE,
+                component: Setup::NAME.into(),
+                setting: Some("ComputerName".into()),
+                message: "setting belongs to a different component".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_command_orders() {
+        let mut unattend = UnattendXml::from_str(UNATTEND_2).unwrap();
+        let orders = |unattend: &UnattendXml| {
+            unattend
+                .settings
+                .iter()
+                .flat_map(|settings| &settings.component)
+                .find_map(|component| match component {
+                    Component::ShellSetup(component) => component.FirstLogonCommands.as_ref(),
+                    _ => None,
+                })
+                .unwrap()
+                .SynchronousCommand
+                .iter()
+                .map(|command| command.Order)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            orders(&unattend),
+            vec![1, 2, 3, 4, 20, 50, 51, 97, 98, 499, 500]
+        );
+
+        for settings in &mut unattend.settings {
+            for component in &mut settings.component {
+                if let Component::ShellSetup(ShellSetup {
+                    FirstLogonCommands: Some(commands),
+                    ..
+                }) = component
+                {
+                    commands.SynchronousCommand[0].Order = 51;
+                    commands.SynchronousCommand[1].Order = 0;
+                    commands.SynchronousCommand[2].Order = 501;
+                }
+            }
+        }
+        let messages: Vec<_> = unattend
+            .validate()
+            .into_iter()
+            .map(|diagnostic| {
+                assert_eq!(diagnostic.setting.as_deref(), Some("FirstLogonCommands"));
+                diagnostic.message
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "order 0 is outside the range 1 to 500",
+                "order 501 is outside the range 1 to 500",
+                "order 51 is used by more than one command",
+            ]
+        );
+
+        unattend.renumber();
+        assert_eq!(orders(&unattend), vec![5, 1, 11, 2, 3, 4, 6, 7, 8, 9, 10]);
+        assert!(unattend.validate().is_empty());
+
+        // Comments stay with their commands
+        let xml = unattend.to_string_with(&WriteOptions::compact()).unwrap();
+        assert!(xml.contains("<!-- Install Chocolatey --><SynchronousCommand wcm:action=\"add\"><CommandLine>cmd /q /c &quot;FOR %i IN (A B C D E F G H I J K L N M O P Q R S T U V W X Y Z) DO IF EXIST %i:\\chocolatey.ps1"));
+    }
+
+    #[test]
+    fn test_validate_disk_layout() {
+        let mut unattend = UnattendXml::from_str(UNATTEND_2).unwrap();
+        for component in &mut unattend.settings[0].component {
+            if let Component::Setup(setup) = component {
+                let disk = setup
+                    .DiskConfiguration
+                    .as_mut()
+                    .unwrap()
+                    .disk_mut(0)
+                    .unwrap();
+                disk.CreatePartitions.as_mut().unwrap().CreatePartition[0].Extend = Some(true);
+                disk.ModifyPartitions.as_mut().unwrap().ModifyPartition[1].PartitionID = 3;
+                let image = setup.ImageInstall.as_mut().unwrap();
+                image.OSImage.InstallTo.as_mut().unwrap().PartitionID = 3;
+            }
+        }
+
+        let diagnostics = unattend.validate();
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.pass == Pass::WindowsPE
+                && diagnostic.component == Setup::NAME));
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (
+                    diagnostic.setting.as_deref().unwrap(),
+                    diagnostic.message.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "DiskConfiguration/Disk/CreatePartitions",
+                    "partition 1 on disk 0 has both Extend and Size"
+                ),
+                (
+                    "DiskConfiguration/Disk/CreatePartitions",
+                    "partition 1 on disk 0 extends to fill the disk but isn't the last partition"
+                ),
+                (
+                    "DiskConfiguration/Disk/CreatePartitions",
+                    "more than one partition on disk 0 extends to fill the disk"
+                ),
+                (
+                    "DiskConfiguration/Disk/ModifyPartitions",
+                    "partition 3 on disk 0 is modified but not created"
+                ),
+                (
+                    "ImageInstall/OSImage/InstallTo",
+                    "partition 3 on disk 0 is not created"
+                ),
+            ]
+        );
+
+        for component in &mut unattend.settings[0].component {
+            if let Component::Setup(setup) = component {
+                let image = setup.ImageInstall.as_mut().unwrap();
+                image.OSImage.InstallTo.as_mut().unwrap().DiskID = 1;
+            }
+        }
+        assert_eq!(
+            unattend.validate().last().unwrap().message,
+            "disk 1 is not configured"
+        );
+
+        // The existing partitions of a disk that isn't wiped are left alone
+        let mut unattend = UnattendXml::from_str(UNATTEND_2).unwrap();
+        let mut set_wipe = |wipe: bool| {
+            for component in &mut unattend.settings[0].component {
+                if let Component::Setup(setup) = component {
+                    let disk = setup
+                        .DiskConfiguration
+                        .as_mut()
+                        .unwrap()
+                        .disk_mut(0)
+                        .unwrap();
+                    disk.WillWipeDisk = wipe;
+                    disk.ModifyPartitions.as_mut().unwrap().ModifyPartition[1].PartitionID = 5;
+                    let image = setup.ImageInstall.as_mut().unwrap();
+                    image.OSImage.InstallTo.as_mut().unwrap().PartitionID = 5;
+                }
+            }
+            unattend.validate()
+        };
+        let diagnostics = set_wipe(false);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let diagnostics = set_wipe(true);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "partition 5 on disk 0 is modified but not created",
+                "partition 5 on disk 0 is not created"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip_partial_disks() {
+        let raw_xml = "<DiskConfiguration><WillShowUI>Always</WillShowUI></DiskConfiguration>";
+        let configuration: DiskConfiguration = de::from_str(raw_xml).unwrap();
+        assert!(configuration.Disk.is_empty());
+        assert_eq!(se::to_string(&configuration).unwrap(), raw_xml);
+
+        let raw_xml = concat!(
+            "<DiskConfiguration>",
+            "<Disk wcm:action=\"add\"><DiskID>0</DiskID><WillWipeDisk>true</WillWipeDisk></Disk>",
+            "<Disk wcm:action=\"add\">",
+            "<CreatePartitions><CreatePartition wcm:action=\"add\">",
+            "<Extend>true</Extend><Order>1</Order><Type>Primary</Type>",
+            "</CreatePartition></CreatePartitions>",
+            "<DiskID>1</DiskID><WillWipeDisk>true</WillWipeDisk>",
+            "</Disk>",
+            "</DiskConfiguration>",
+        );
+        let configuration: DiskConfiguration = de::from_str(raw_xml).unwrap();
+        let wiped = configuration.disk(0).unwrap();
+        assert!(wiped.CreatePartitions.is_none());
+        assert!(wiped.ModifyPartitions.is_none());
+        let data = configuration.disk(1).unwrap();
+        assert_eq!(
+            data.CreatePartitions
+                .as_ref()
+                .unwrap()
+                .CreatePartition
+                .len(),
+            1
+        );
+        assert!(data.ModifyPartitions.is_none());
+        assert_eq!(se::to_string(&configuration).unwrap(), raw_xml);
+    }
+
+    #[test]
+    fn test_round_trip_multiple_disks() {
+        let raw_xml = r#"
+            <DiskConfiguration>
+              <Disk wcm:action="add">
+                <CreatePartitions>
+                  <CreatePartition wcm:action="add">
+                    <Extend>true</Extend>
+                    <Order>1</Order>
+                    <Type>Primary</Type>
+                  </CreatePartition>
+                </CreatePartitions>
+                <DiskID>0</DiskID>
+                <ModifyPartitions>
+                  <ModifyPartition wcm:action="add">
+                    <Format>NTFS</Format>
+                    <Label>OS</Label>
+                    <Letter>C</Letter>
+                    <Order>1</Order>
+                    <PartitionID>1</PartitionID>
+                  </ModifyPartition>
+                </ModifyPartitions>
+                <WillWipeDisk>true</WillWipeDisk>
+              </Disk>
+              <Disk wcm:action="add">
+                <CreatePartitions>
+                  <CreatePartition wcm:action="add">
+                    <Extend>true</Extend>
+                    <Order>1</Order>
+                    <Type>Primary</Type>
+                  </CreatePartition>
+                </CreatePartitions>
+                <DiskID>1</DiskID>
+                <ModifyPartitions>
+                  <ModifyPartition wcm:action="add">
+                    <Format>NTFS</Format>
+                    <Label>Data</Label>
+                    <Letter>D</Letter>
+                    <Order>1</Order>
+                    <PartitionID>1</PartitionID>
+                  </ModifyPartition>
+                </ModifyPartitions>
+                <WillWipeDisk>true</WillWipeDisk>
+              </Disk>
+            </DiskConfiguration>
+        "#;
+        let mut configuration: DiskConfiguration = de::from_str(raw_xml).unwrap();
+        assert_eq!(configuration.Disk.len(), 2);
+        let data = configuration.disk(1).unwrap();
+        assert_eq!(
+            data.ModifyPartitions.as_ref().unwrap().ModifyPartition[0]
+                .Label
+                .as_deref(),
+            Some("Data")
+        );
+        assert!(configuration.disk(2).is_none());
+        let xml = se::to_string(&configuration).unwrap();
+        assert_eq!(xml.matches("<Disk wcm:action=\"add\">").count(), 2);
+        assert_eq!(
+            se::to_string(&de::from_str::<DiskConfiguration>(&xml).unwrap()).unwrap(),
+            xml
+        );
+
+        let mut disk = configuration.disk(1).unwrap().clone();
+        disk.WillWipeDisk = false;
+        configuration.set_disk(disk);
+        assert_eq!(configuration.Disk.len(), 2);
+        assert!(!configuration.disk(1).unwrap().WillWipeDisk);
+
+        let mut disk = configuration.disk(1).unwrap().clone();
+        disk.DiskID = 0;
+        configuration.Disk.push(disk);
+        let setup = Setup {
+            DiskConfiguration: Some(configuration),
+            ..Default::default()
+        };
+        let unattend = UnattendXml {
+            xmlns: "urn:schemas-microsoft-com:unattend".into(),
+            servicing: None,
+            settings: vec![Settings {
+                component: vec![Component::Setup(setup)],
+                pass: Pass::WindowsPE,
+                preserved: Preserved::default(),
+            }],
+            offlineImage: None,
+            preserved: Preserved::default(),
+            prolog: Vec::new(),
+        };
+        assert_eq!(
+            unattend.validate()[0].message,
+            "disk 0 is configured more than once"
+        );
+    }
+
+    #[test]
+    fn test_validate_modified_extend() {
+        let mut unattend = UnattendXml::from_str(UNATTEND_2).unwrap();
+        for component in &mut unattend.settings[0].component {
+            if let Component::Setup(setup) = component {
+                let disk = setup
+                    .DiskConfiguration
+                    .as_mut()
+                    .unwrap()
+                    .disk_mut(0)
+                    .unwrap();
+                let modified = &mut disk.ModifyPartitions.as_mut().unwrap().ModifyPartition;
+                modified[0].Extend = Some(true);
+                modified[1].Extend = Some(true);
+            }
+        }
+        assert_eq!(
+            unattend
+                .validate()
+                .iter()
+                .map(|diagnostic| diagnostic.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "partition 1 on disk 0 extends to fill the disk but is created with a Size",
+                "partition 1 on disk 0 extends to fill the disk but isn't the last partition",
+                "more than one modified partition on disk 0 extends to fill the disk",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip_partitions() {
+        let unattend = UnattendXml::from_str(UNATTEND_2).unwrap();
+        let disk = unattend.settings[0]
+            .component
+            .iter()
+            .find_map(|component| match component {
+                Component::Setup(setup) => setup.DiskConfiguration.as_ref(),
+                _ => None,
+            })
+            .and_then(|configuration| configuration.disk(0))
+            .unwrap();
+        let created = &disk.CreatePartitions.as_ref().unwrap().CreatePartition;
+        assert_eq!(created[0].Type, PartitionType::Primary);
+        let modified = &disk.ModifyPartitions.as_ref().unwrap().ModifyPartition;
+        assert_eq!(modified[0].Active, Some(true));
+        assert_eq!(modified[0].TypeID.as_deref(), Some("0x27"));
+        assert!(modified[0].preserved.unknown.is_empty());
+
+        let raw_xml = r#"
+            <ModifyPartitions>
+              <ModifyPartition>
+                <Order>3</Order>
+                <PartitionID>3</PartitionID>
+              </ModifyPartition>
+              <ModifyPartition>
+                <Extend>false</Extend>
+                <Format>FAT32</Format>
+                <Label>System</Label>
+                <Order>2</Order>
+                <PartitionID>2</PartitionID>
+                <TypeID>c12a7328-f81f-11d2-ba4b-00a0c93ec93b</TypeID>
+              </ModifyPartition>
+            </ModifyPartitions>
         "#;
+        let partitions: ModifyPartitions = de::from_str(raw_xml).unwrap();
+        assert_eq!(partitions.ModifyPartition[0].Format, None);
+        assert_eq!(partitions.ModifyPartition[0].Label, None);
+        assert_eq!(
+            se::to_string(&partitions).unwrap(),
+            concat!(
+                "<ModifyPartitions>",
+                "<ModifyPartition wcm:action=\"add\"><Order>3</Order><PartitionID>3</PartitionID></ModifyPartition>",
+                "<ModifyPartition wcm:action=\"add\"><Extend>false</Extend><Format>FAT32</Format><Label>System</Label>",
+                "<Order>2</Order><PartitionID>2</PartitionID><TypeID>c12a7328-f81f-11d2-ba4b-00a0c93ec93b</TypeID>",
+                "</ModifyPartition>",
+                "</ModifyPartitions>",
+            )
+        );
 
-        let _: UnattendXml = quick_xml::de::from_str(raw_xml).unwrap();
+        let partition: CreatePartition = de::from_str(
+            "<CreatePartition><Order>2</Order><Size>16</Size><Type>MSR</Type></CreatePartition>",
+        )
+        .unwrap();
+        assert_eq!(partition.Type, PartitionType::MSR);
+        assert!(de::from_str::<CreatePartition>(
+            "<CreatePartition><Order>2</Order><Type>Basic</Type></CreatePartition>"
+        )
+        .is_err());
     }
 }
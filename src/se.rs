@@ -0,0 +1,520 @@
+//! Serialize the typed bindings into an [`Element`] tree.
+//!
+//! This mirrors [`crate::de`]: `@name` fields become attributes, `$text` becomes the
+//! text content and every other field becomes one child element per value. The
+//! content of the [`PRESERVED`] field is written directly into the element, with
+//! its comments placed in front of the children they are anchored to.
+
+use crate::de::{self, COMMENTS, PRESERVED};
+use crate::dom::{write_event, Comment, Element, Indent, Node, RAW_ELEMENT};
+use crate::{Error, NamespaceDeclarations, WriteOptions};
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::Writer;
+use serde::ser::{self, Impossible, Serialize};
+
+#[cfg(test)]
+pub(crate) fn to_string<T: Serialize>(value: &T) -> Result<String, Error> {
+    Ok(to_element(value)?.to_string())
+}
+
+/// Write a document with the given comments before its root element.
+pub(crate) fn to_string_with<T: Serialize>(
+    value: &T,
+    prolog: &[String],
+    options: &WriteOptions,
+) -> Result<String, Error> {
+    let mut element = to_element(value)?;
+    if options.namespaces == NamespaceDeclarations::Root {
+        element.hoist_namespaces();
+    }
+    let indent = options.indent.map(|width| Indent {
+        line_ending: options.line_ending.as_str(),
+        width,
+    });
+
+    let mut writer = Writer::new(Vec::new());
+    if options.declaration {
+        write_event(
+            &mut writer,
+            Event::Decl(BytesDecl::new("1.0", Some(options.encoding.as_str()), None)),
+        )?;
+        if let Some(indent) = indent {
+            indent.write(&mut writer, 0)?;
+        }
+    }
+    for comment in prolog {
+        write_event(
+            &mut writer,
+            Event::Comment(BytesText::from_escaped(comment)),
+        )?;
+        if let Some(indent) = indent {
+            indent.write(&mut writer, 0)?;
+        }
+    }
+    element.write(&mut writer, indent)?;
+
+    let xml = String::from_utf8(writer.into_inner())
+        .map_err(|error| Error::Serialize(error.to_string()))?;
+    Ok(if options.bom {
+        format!("\u{feff}{xml}")
+    } else {
+        xml
+    })
+}
+
+pub(crate) fn to_element<T: Serialize>(value: &T) -> Result<Element, Error> {
+    let mut nodes = Vec::new();
+    value.serialize(NodeSerializer {
+        name: None,
+        nodes: &mut nodes,
+    })?;
+
+    match nodes.pop() {
+        Some(Node::Element(element)) if nodes.is_empty() => Ok(element),
+        _ => Err(Error::Serialize("expected a single root element".into())),
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: std::fmt::Display>(message: T) -> Self {
+        Error::Serialize(message.to_string())
+    }
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::Serialize(format!("{what} is not supported"))
+}
+
+/// Serializes a value into zero or more nodes named after the field that holds it,
+/// or after the struct itself at the root.
+struct NodeSerializer<'a> {
+    name: Option<&'static str>,
+    nodes: &'a mut Vec<Node>,
+}
+
+impl NodeSerializer<'_> {
+    fn text(self, text: String) -> Result<(), Error> {
+        let mut element = Element::new(
+            self.name
+                .ok_or_else(|| Error::Serialize("expected a struct at the root".into()))?,
+        );
+        if !text.is_empty() {
+            element.children.push(Node::Text(text));
+        }
+        self.nodes.push(Node::Element(element));
+        Ok(())
+    }
+}
+
+macro_rules! serialize_display {
+    ($($method:ident: $ty:ty,)*) => {
+        $(
+            fn $method(self, value: $ty) -> Result<(), Error> {
+                self.text(value.to_string())
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for NodeSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = StructSerializer<'a>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    serialize_display! {
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<(), Error> {
+        Err(unsupported("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.text(String::new())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.text(String::new())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.text(variant.into())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        if name == RAW_ELEMENT {
+            let xml = value.serialize(TextSerializer)?.unwrap_or_default();
+            self.nodes.push(Node::Element(Element::parse(&xml)?));
+            Ok(())
+        } else {
+            value.serialize(self)
+        }
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(unsupported("enum with data"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Ok(SeqSerializer {
+            name: self.name,
+            nodes: self.nodes,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unsupported("enum with data"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(unsupported("map"))
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Ok(StructSerializer {
+            element: Element::new(self.name.unwrap_or(name)),
+            comments: Vec::new(),
+            nodes: self.nodes,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unsupported("enum with data"))
+    }
+}
+
+/// Serializes each item as a sibling element with the same name.
+struct SeqSerializer<'a> {
+    name: Option<&'static str>,
+    nodes: &'a mut Vec<Node>,
+}
+
+impl SeqSerializer<'_> {
+    fn item<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(NodeSerializer {
+            name: self.name,
+            nodes: self.nodes,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+struct StructSerializer<'a> {
+    element: Element,
+    comments: Vec<Node>,
+    nodes: &'a mut Vec<Node>,
+}
+
+impl ser::SerializeStruct for StructSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        if let Some(name) = key.strip_prefix('@') {
+            if let Some(value) = value.serialize(TextSerializer)? {
+                self.element.attributes.push((name.into(), value));
+            }
+        } else if key == "$text" {
+            if let Some(text) = value.serialize(TextSerializer)? {
+                self.element.children.push(Node::Text(text));
+            }
+        } else if key == COMMENTS {
+            value.serialize(NodeSerializer {
+                name: Some(key),
+                nodes: &mut self.comments,
+            })?;
+        } else if key == PRESERVED {
+            let mut nodes = Vec::new();
+            value.serialize(NodeSerializer {
+                name: Some(key),
+                nodes: &mut nodes,
+            })?;
+            for node in nodes {
+                let Node::Element(preserved) = node else {
+                    continue;
+                };
+                for child in preserved.children {
+                    match child {
+                        Node::Element(element) if element.name == COMMENTS => {
+                            self.comments.push(Node::Element(element))
+                        }
+                        child => self.element.children.push(child),
+                    }
+                }
+            }
+        } else {
+            value.serialize(NodeSerializer {
+                name: Some(key),
+                nodes: &mut self.element.children,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn end(mut self) -> Result<(), Error> {
+        if self.element.name == PRESERVED {
+            // Handed on to the element that holds the preserved content
+            self.element.children.append(&mut self.comments);
+        } else {
+            // Comments are placed last, once the children they are anchored to exist
+            let comments = self
+                .comments
+                .iter()
+                .filter_map(|node| match node {
+                    Node::Element(element) => Some(de::from_element::<Comment>(element)),
+                    _ => None,
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            self.element.insert_comments(comments);
+        }
+        self.nodes.push(Node::Element(self.element));
+        Ok(())
+    }
+}
+
+/// Serializes a simple value into the text of an attribute or element, if present.
+struct TextSerializer;
+
+macro_rules! serialize_text {
+    ($($method:ident: $ty:ty,)*) => {
+        $(
+            fn $method(self, value: $ty) -> Result<Option<String>, Error> {
+                Ok(Some(value.to_string()))
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for TextSerializer {
+    type Ok = Option<String>;
+    type Error = Error;
+    type SerializeSeq = Impossible<Option<String>, Error>;
+    type SerializeTuple = Impossible<Option<String>, Error>;
+    type SerializeTupleStruct = Impossible<Option<String>, Error>;
+    type SerializeTupleVariant = Impossible<Option<String>, Error>;
+    type SerializeMap = Impossible<Option<String>, Error>;
+    type SerializeStruct = Impossible<Option<String>, Error>;
+    type SerializeStructVariant = Impossible<Option<String>, Error>;
+
+    serialize_text! {
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<Option<String>, Error> {
+        Err(unsupported("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Option<String>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Option<String>, Error> {
+        Ok(Some(String::new()))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Option<String>, Error> {
+        Ok(Some(String::new()))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Option<String>, Error> {
+        Ok(Some(variant.into()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Option<String>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Option<String>, Error> {
+        Err(unsupported("enum with data in text"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(unsupported("sequence in text"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(unsupported("tuple in text"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(unsupported("tuple in text"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unsupported("enum with data in text"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(unsupported("map in text"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(unsupported("struct in text"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unsupported("enum with data in text"))
+    }
+}
@@ -0,0 +1,334 @@
+//! Checks for mistakes that Windows Setup silently ignores.
+
+use crate::{
+    Component, CreatePartition, Deployment, Disk, Pass, Setup, ShellSetup, UnattendXml,
+    MAX_COMMAND_ORDER,
+};
+use std::collections::HashSet;
+use std::fmt;
+
+/// A problem found in an answer file by [`UnattendXml::validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The configuration pass containing the problem.
+    pub pass: Pass,
+    /// The name of the component containing the problem.
+    pub component: String,
+    /// The path of the setting within the component, if the problem is with a setting
+    /// rather than the whole component.
+    pub setting: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} / {}", self.pass, self.component)?;
+        if let Some(setting) = &self.setting {
+            write!(f, " / {setting}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+pub(crate) fn validate(unattend: &UnattendXml) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for settings in &unattend.settings {
+        for component in &settings.component {
+            let mut report = |setting: Option<&str>, message: String| {
+                diagnostics.push(Diagnostic {
+                    pass: settings.pass,
+                    component: component.name().into(),
+                    setting: setting.map(Into::into),
+                    message,
+                })
+            };
+
+            for setting in component.misplaced_settings() {
+                report(
+                    Some(setting),
+                    "setting belongs to a different component".into(),
+                );
+            }
+            match passes(component) {
+                Some(passes) if !passes.contains(&settings.pass) => {
+                    report(
+                        None,
+                        format!("component is not valid in the {} pass", settings.pass),
+                    );
+                    continue;
+                }
+                _ => {}
+            }
+            for (setting, passes) in settings_passes(component) {
+                if !passes.contains(&settings.pass) {
+                    report(
+                        Some(setting),
+                        format!("setting is not valid in the {} pass", settings.pass),
+                    );
+                }
+            }
+
+            if let Component::Setup(setup) = component {
+                for (setting, message) in disk_layout(setup) {
+                    report(Some(setting), message);
+                }
+            }
+
+            if let Some((setting, orders)) = command_orders(component) {
+                let mut seen = HashSet::new();
+                let mut duplicates = HashSet::new();
+                for order in orders {
+                    if !(1..=MAX_COMMAND_ORDER).contains(&order) {
+                        report(
+                            Some(setting),
+                            format!("order {order} is outside the range 1 to {MAX_COMMAND_ORDER}"),
+                        );
+                    }
+                    if !seen.insert(order) && duplicates.insert(order) {
+                        report(
+                            Some(setting),
+                            format!("order {order} is used by more than one command"),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+/// The passes a component can be used in, if it's known.
+fn passes(component: &Component) -> Option<&'static [Pass]> {
+    use Pass::*;
+    Some(match component {
+        Component::InternationalCoreWinPE(_) => &[WindowsPE],
+        Component::InternationalCore(_) => &[Specialize, OobeSystem],
+        Component::Setup(_) => &[WindowsPE],
+        Component::ShellSetup(_) => &[
+            OfflineServicing,
+            Generalize,
+            Specialize,
+            AuditSystem,
+            AuditUser,
+            OobeSystem,
+        ],
+        Component::Deployment(_) => &[Generalize, Specialize, AuditSystem, AuditUser, OobeSystem],
+        Component::PnpCustomizationsWinPE(_) => &[WindowsPE],
+        Component::PnpCustomizationsNonWinPE(_) => &[OfflineServicing, AuditSystem],
+        Component::Other(_) => return None,
+    })
+}
+
+/// The settings present in a component which are valid in fewer passes than the
+/// component itself, with the passes each is valid in.
+fn settings_passes(component: &Component) -> Vec<(&'static str, &'static [Pass])> {
+    use Pass::*;
+    match component {
+        Component::ShellSetup(component) => [
+            (
+                "AutoLogon",
+                component.AutoLogon.is_some(),
+                &[Specialize, AuditSystem, OobeSystem][..],
+            ),
+            (
+                "ComputerName",
+                component.ComputerName.is_some(),
+                &[OfflineServicing, Specialize],
+            ),
+            (
+                "FirstLogonCommands",
+                component.FirstLogonCommands.is_some(),
+                &[OobeSystem],
+            ),
+            ("OOBE", component.OOBE.is_some(), &[OobeSystem]),
+            (
+                "UserAccounts",
+                component.UserAccounts.is_some(),
+                &[AuditSystem, OobeSystem],
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(setting, present, passes)| present.then_some((setting, passes)))
+        .collect(),
+        Component::Deployment(component) if component.RunSynchronous.is_some() => {
+            vec![("RunSynchronous", &[Specialize, AuditUser])]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// The orders of the commands in a component, in the order they appear.
+fn command_orders(component: &Component) -> Option<(&'static str, Vec<u32>)> {
+    match component {
+        Component::Setup(Setup {
+            RunSynchronous: Some(commands),
+            ..
+        })
+        | Component::Deployment(Deployment {
+            RunSynchronous: Some(commands),
+            ..
+        }) => Some((
+            "RunSynchronous",
+            commands
+                .RunSynchronousCommand
+                .iter()
+                .map(|command| command.Order)
+                .collect(),
+        )),
+        Component::ShellSetup(ShellSetup {
+            FirstLogonCommands: Some(commands),
+            ..
+        }) => Some((
+            "FirstLogonCommands",
+            commands
+                .SynchronousCommand
+                .iter()
+                .map(|command| command.Order)
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+/// Problems with the partitions Setup is asked to create, modify and install to.
+fn disk_layout(setup: &Setup) -> Vec<(&'static str, String)> {
+    let mut problems = Vec::new();
+    let Some(configuration) = &setup.DiskConfiguration else {
+        return problems;
+    };
+    let mut ids = HashSet::new();
+    for disk in &configuration.Disk {
+        if !ids.insert(disk.DiskID) {
+            problems.push((
+                "DiskConfiguration/Disk",
+                format!("disk {} is configured more than once", disk.DiskID),
+            ));
+        }
+
+        let created = created(disk);
+        let last = created.iter().map(|partition| partition.Order).max();
+        let mut extended = 0;
+        for partition in created {
+            if partition.Extend == Some(true) {
+                extended += 1;
+                if partition.Size.is_some() {
+                    problems.push((
+                        "DiskConfiguration/Disk/CreatePartitions",
+                        format!(
+                            "partition {} on disk {} has both Extend and Size",
+                            partition.Order, disk.DiskID
+                        ),
+                    ));
+                }
+                if Some(partition.Order) != last {
+                    problems.push((
+                        "DiskConfiguration/Disk/CreatePartitions",
+                        format!(
+                            "partition {} on disk {} extends to fill the disk but isn't the last partition",
+                            partition.Order, disk.DiskID
+                        ),
+                    ));
+                }
+            }
+        }
+        if extended > 1 {
+            problems.push((
+                "DiskConfiguration/Disk/CreatePartitions",
+                format!(
+                    "more than one partition on disk {} extends to fill the disk",
+                    disk.DiskID
+                ),
+            ));
+        }
+
+        let modified = disk
+            .ModifyPartitions
+            .iter()
+            .flat_map(|partitions| &partitions.ModifyPartition);
+        let mut extended = 0;
+        for partition in modified {
+            if !creates(disk, partition.PartitionID) {
+                problems.push((
+                    "DiskConfiguration/Disk/ModifyPartitions",
+                    format!(
+                        "partition {} on disk {} is modified but not created",
+                        partition.PartitionID, disk.DiskID
+                    ),
+                ));
+            }
+            if partition.Extend == Some(true) {
+                extended += 1;
+                let sized = created.iter().any(|created| {
+                    created.Order == partition.PartitionID && created.Size.is_some()
+                });
+                if sized {
+                    problems.push((
+                        "DiskConfiguration/Disk/ModifyPartitions",
+                        format!(
+                            "partition {} on disk {} extends to fill the disk but is created with a Size",
+                            partition.PartitionID, disk.DiskID
+                        ),
+                    ));
+                }
+                // Only the partitions of a wiped disk are known
+                if disk.WillWipeDisk && Some(partition.PartitionID) != last {
+                    problems.push((
+                        "DiskConfiguration/Disk/ModifyPartitions",
+                        format!(
+                            "partition {} on disk {} extends to fill the disk but isn't the last partition",
+                            partition.PartitionID, disk.DiskID
+                        ),
+                    ));
+                }
+            }
+        }
+        if extended > 1 {
+            problems.push((
+                "DiskConfiguration/Disk/ModifyPartitions",
+                format!(
+                    "more than one modified partition on disk {} extends to fill the disk",
+                    disk.DiskID
+                ),
+            ));
+        }
+    }
+
+    let install_to = setup
+        .ImageInstall
+        .as_ref()
+        .and_then(|image| image.OSImage.InstallTo.as_ref());
+    if let Some(install_to) = install_to {
+        match configuration.disk(install_to.DiskID) {
+            None => problems.push((
+                "ImageInstall/OSImage/InstallTo",
+                format!("disk {} is not configured", install_to.DiskID),
+            )),
+            Some(disk) if !creates(disk, install_to.PartitionID) => problems.push((
+                "ImageInstall/OSImage/InstallTo",
+                format!(
+                    "partition {} on disk {} is not created",
+                    install_to.PartitionID, disk.DiskID
+                ),
+            )),
+            Some(_) => {}
+        }
+    }
+    problems
+}
+
+/// Whether the partition will exist after the disk is configured. A wiped disk only
+/// has the partitions it creates, numbered in the order they're created, while the
+/// existing partitions of a disk that isn't wiped can't be known.
+fn creates(disk: &Disk, partition: u32) -> bool {
+    !disk.WillWipeDisk
+        || created(disk)
+            .iter()
+            .any(|created| created.Order == partition)
+}
+
+/// The partitions created on a disk, if any.
+fn created(disk: &Disk) -> &[CreatePartition] {
+    disk.CreatePartitions
+        .as_ref()
+        .map_or(&[], |partitions| &partitions.CreatePartition)
+}